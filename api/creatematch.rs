@@ -0,0 +1,52 @@
+use opinion_api::auth::authenticate;
+use opinion_api::{
+    MatchDoc, error_response, is_duplicate_key, json_response, matches, random_code,
+};
+use opinion_rules::Tern;
+use serde_json::json;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+/// Match codes are short enough to read out loud, so retry on the odd collision.
+const CODE_ATTEMPTS: usize = 5;
+
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let username = match authenticate(&req) {
+        Ok(username) => username,
+        Err(resp) => return Ok(resp),
+    };
+
+    let matches = matches().await?;
+    let seed: u64 = rand::random();
+
+    for _ in 0..CODE_ATTEMPTS {
+        let doc = MatchDoc::new(random_code(6), seed, &username);
+        match matches.insert_one(&doc).await {
+            Ok(_) => {
+                return json_response(
+                    StatusCode::CREATED,
+                    json!({
+                        "match_id": doc.id,
+                        "side": Tern::Player1,
+                        "seed": seed,
+                    }),
+                );
+            }
+            Err(e) if is_duplicate_key(&e) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    error_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "could not allocate a match code",
+    )
+}
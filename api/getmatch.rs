@@ -0,0 +1,29 @@
+use mongodb::bson::doc;
+use opinion_api::{error_response, json_response, matches, query_param};
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+/// `?match_id=CODE&since=N` returns the match state with the actions from index `N` on.
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    let Some(match_id) = query_param(&req, "match_id") else {
+        return error_response(StatusCode::BAD_REQUEST, "missing match_id");
+    };
+    let since = match query_param(&req, "since").map(|s| s.parse::<usize>()) {
+        None => 0,
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return error_response(StatusCode::BAD_REQUEST, "since must be a number"),
+    };
+
+    let matches = matches().await?;
+    match matches
+        .find_one(doc! { "_id": match_id.to_ascii_uppercase() })
+        .await?
+    {
+        Some(game) => json_response(StatusCode::OK, game.public_view(since)),
+        None => error_response(StatusCode::NOT_FOUND, "no match with this code"),
+    }
+}
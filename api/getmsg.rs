@@ -1,17 +1,10 @@
-
-
-
-use serde::{Deserialize, Serialize};
+use futures::stream::TryStreamExt;
+use mongodb::bson::doc;
+use opinion_api::{
+    ChatCursor, ChatMessage, chats, ensure_chat_index, error_response, json_response, query_param,
+};
 use serde_json::json;
 use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
-use mongodb::{ 
-	bson::{doc},
-	Client,
-	Collection 
-};
-use futures::stream::TryStreamExt;
-
-
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -19,45 +12,118 @@ async fn main() -> Result<(), Error> {
 }
 
 #[test]
-fn getmsg_test()  {
+fn getmsg_test() {
+    if std::env::var_os("MONGODB_URI").is_none() {
+        // SAFETY: the only test of this binary, nothing else reads the environment yet
+        unsafe { std::env::set_var("MONGODB_URI", "mongodb://localhost:27017") };
+    }
     let rt = tokio::runtime::Runtime::new().unwrap();
     rt.block_on(async {
-	handler(Request::new(Body::Empty)).await.unwrap();
-    }
-    );
-}
-#[derive(Debug, Deserialize, Serialize)]
-struct Message {
-    sender: String,
-    message: String
+        handler(Request::new(Body::Empty)).await.unwrap();
+    });
 }
 
-pub async fn handler(_req: Request) -> Result<Response<Body>, Error> {
-    #[cfg(test)]
-    let uri = "mongodb://localhost:27017";
-    #[cfg(not(test))]
-    let uri = std::env::var("MONGODB_URI")?;
-
-    // Create a new client and connect to the server
-    let client = Client::with_uri_str(uri).await?;
-
-    // Get a handle on the movies collection
-    let database = client.database("opinion");
-    let chats: Collection<Message> = database.collection("chat");
-    let mut cursor = chats.find(doc!{}).await?;
-    let mut ms: Vec<Message> = vec!{};
-    while let Some(m) = cursor.try_next().await?  {
-	ms.push(m);
+/// Messages per page when `limit` is left out.
+const DEFAULT_LIMIT: i64 = 50;
+
+/// Most messages a single page may hold.
+const MAX_LIMIT: i64 = 100;
+
+/// `?room=R&limit=N` returns the latest `N` messages of room `R`, oldest first.
+/// Adding `before=C` (a `prev_cursor` from an earlier reply) returns the `N`
+/// messages right before that point instead, to page back through the
+/// history. Adding `cursor=C` (a `next_cursor`) or `since=T` (milliseconds
+/// since the epoch) returns the messages after that point, to page forward
+/// or poll for new ones.
+///
+/// Pages going back tell with `has_older` whether there are older messages
+/// than the page, pages going forward tell with `has_newer` whether there
+/// are newer ones.
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    let limit = match query_param(&req, "limit").map(|s| s.parse::<i64>()) {
+        None => DEFAULT_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("limit must be a number from 1 to {}", MAX_LIMIT),
+            );
+        }
+    };
+
+    let mut filter = doc! {};
+    if let Some(room) = query_param(&req, "room") {
+        filter.insert("room", room);
+    }
+
+    let parse_cursor = |name| match query_param(&req, name) {
+        Some(text) => ChatCursor::parse(&text).map(|c| Some((text, c))).ok_or(()),
+        None => Ok(None),
+    };
+    let (Ok(cursor), Ok(before)) = (parse_cursor("cursor"), parse_cursor("before")) else {
+        return error_response(StatusCode::BAD_REQUEST, "invalid cursor");
+    };
+    let since = match query_param(&req, "since").map(|s| s.parse::<i64>()) {
+        None => None,
+        Some(Ok(since)) => Some(since),
+        Some(Err(_)) => return error_response(StatusCode::BAD_REQUEST, "since must be a number"),
+    };
+    let after = match (&cursor, since) {
+        (Some((_, cursor)), _) => Some(cursor.filter_after()),
+        (None, Some(since)) => Some(doc! { "sent_at": { "$gt": since } }),
+        (None, None) => None,
+    };
+    if after.is_some() && before.is_some() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "before can't be combined with cursor or since",
+        );
+    }
+
+    let chats = chats().await?;
+    ensure_chat_index(&chats).await?;
+
+    // one extra message tells whether there is more to fetch
+    let forward = after.is_some();
+    let order = if forward { 1 } else { -1 };
+    if let Some(after) = after {
+        filter.extend(after);
+    }
+    if let Some((_, before)) = &before {
+        filter.extend(before.filter_before());
     }
-    //for m in messages.iter() {
-    println!("message:\n{:#?}", ms);
-    //}
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/json")
-        .body(
-            json!({"data": ms})
-            .to_string()
-            .into(),
-        )?)
+    let mut ms: Vec<ChatMessage> = chats
+        .find(filter)
+        .sort(doc! { "sent_at": order, "_id": order })
+        .limit(limit + 1)
+        .await?
+        .try_collect()
+        .await?;
+    let has_more = ms.len() as i64 > limit;
+    ms.truncate(limit as usize);
+    if !forward {
+        ms.reverse();
+    }
+
+    // keep handing back the same cursors while a page is empty, so clients
+    // can poll with them
+    let next_cursor = ms
+        .last()
+        .and_then(ChatCursor::at)
+        .map(|c| c.to_string())
+        .or(cursor.map(|(text, _)| text));
+    let prev_cursor = ms
+        .first()
+        .and_then(ChatCursor::at)
+        .map(|c| c.to_string())
+        .or(before.map(|(text, _)| text));
+
+    let mut reply = json!({
+        "data": ms.iter().map(ChatMessage::public_view).collect::<Vec<_>>(),
+        "next_cursor": next_cursor,
+        "prev_cursor": prev_cursor,
+    });
+    let more_key = if forward { "has_newer" } else { "has_older" };
+    reply[more_key] = json!(has_more);
+    json_response(StatusCode::OK, reply)
 }
@@ -0,0 +1,70 @@
+use mongodb::bson::doc;
+use opinion_api::auth::authenticate;
+use opinion_api::{MatchStatus, OUTDATED_MATCH, error_response, json_body, json_response, matches};
+use opinion_rules::Tern;
+use serde::Deserialize;
+use serde_json::json;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinRequest {
+    match_id: String,
+}
+
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let username = match authenticate(&req) {
+        Ok(username) => username,
+        Err(resp) => return Ok(resp),
+    };
+    let body: JoinRequest = match json_body(&req) {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+    let match_id = body.match_id.trim().to_ascii_uppercase();
+
+    let matches = matches().await?;
+    let Some(game) = matches.find_one(doc! { "_id": &match_id }).await? else {
+        return error_response(StatusCode::NOT_FOUND, "no match with this code");
+    };
+    if game.side_of(&username).is_some() {
+        return error_response(StatusCode::CONFLICT, "cannot join your own match");
+    }
+    if !game.is_current() {
+        return error_response(StatusCode::GONE, OUTDATED_MATCH);
+    }
+    if game.status != MatchStatus::Waiting {
+        return error_response(StatusCode::CONFLICT, "match is already full");
+    }
+
+    // only one of two racing joins may take the second seat
+    let result = matches
+        .update_one(
+            doc! { "_id": &match_id, "status": "waiting" },
+            doc! {
+                "$set": { "status": "playing" },
+                "$push": { "players": &username },
+            },
+        )
+        .await?;
+    if result.modified_count == 0 {
+        return error_response(StatusCode::CONFLICT, "match is already full");
+    }
+
+    json_response(
+        StatusCode::OK,
+        json!({
+            "match_id": match_id,
+            "side": Tern::Player2,
+            "seed": game.seed as u64,
+        }),
+    )
+}
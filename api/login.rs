@@ -0,0 +1,53 @@
+use mongodb::bson::doc;
+use opinion_api::auth::{issue_token, now_secs, session_secret};
+use opinion_api::{accounts, error_response, json_body, json_response};
+use serde::Deserialize;
+use serde_json::json;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+/// POST `{username, password}` returns a fresh session token.
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let body: LoginRequest = match json_body(&req) {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+    let Ok(secret) = session_secret() else {
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "sessions are not configured",
+        );
+    };
+
+    let username = body.username.trim().to_ascii_lowercase();
+    let account = accounts()
+        .await?
+        .find_one(doc! { "_id": &username })
+        .await?;
+
+    // same reply for an unknown name and a wrong password
+    match account {
+        Some(account) if account.check_password(&body.password) => json_response(
+            StatusCode::OK,
+            json!({
+                "username": account.username,
+                "token": issue_token(&secret, &account.username, now_secs()),
+            }),
+        ),
+        _ => error_response(StatusCode::UNAUTHORIZED, "wrong username or password"),
+    }
+}
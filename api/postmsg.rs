@@ -0,0 +1,43 @@
+use opinion_api::auth::authenticate;
+use opinion_api::{ChatMessage, chats, error_response, json_body, json_response};
+use serde::Deserialize;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+#[derive(Debug, Deserialize)]
+struct PostRequest {
+    message: String,
+    /// Match code or room name, `lobby` when left out.
+    room: Option<String>,
+}
+
+/// POST `{message, room?}` stores a chat message from the logged in player
+/// and returns it.
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let sender = match authenticate(&req) {
+        Ok(username) => username,
+        Err(resp) => return Ok(resp),
+    };
+    let body: PostRequest = match json_body(&req) {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+
+    let mut msg = match ChatMessage::new(&sender, &body.message, body.room.as_deref()) {
+        Ok(msg) => msg,
+        Err(e) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, &e),
+    };
+
+    let result = chats().await?.insert_one(&msg).await?;
+    msg.id = result.inserted_id.as_object_id();
+
+    json_response(StatusCode::CREATED, msg.public_view())
+}
@@ -0,0 +1,60 @@
+use opinion_api::auth::{
+    Account, issue_token, now_secs, session_secret, validate_password, validate_username,
+};
+use opinion_api::{accounts, error_response, is_duplicate_key, json_body, json_response};
+use serde::Deserialize;
+use serde_json::json;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+#[derive(Debug, Deserialize)]
+struct RegisterRequest {
+    username: String,
+    password: String,
+}
+
+/// POST `{username, password}` creates an account and logs it in.
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let body: RegisterRequest = match json_body(&req) {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+    let username = match validate_username(&body.username) {
+        Ok(username) => username,
+        Err(e) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, &e),
+    };
+    if let Err(e) = validate_password(&body.password) {
+        return error_response(StatusCode::UNPROCESSABLE_ENTITY, &e);
+    }
+    let Ok(secret) = session_secret() else {
+        return error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "sessions are not configured",
+        );
+    };
+
+    let account = Account::new(&username, &body.password);
+    match accounts().await?.insert_one(&account).await {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key(&e) => {
+            return error_response(StatusCode::CONFLICT, "username is taken");
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    json_response(
+        StatusCode::CREATED,
+        json!({
+            "username": username,
+            "token": issue_token(&secret, &username, now_secs()),
+        }),
+    )
+}
@@ -0,0 +1,72 @@
+use mongodb::bson::doc;
+use opinion_api::auth::authenticate;
+use opinion_api::{card_collection, error_response, json_body, json_response, matches};
+use opinion_rules::GameAction;
+use serde::Deserialize;
+use serde_json::json;
+use vercel_runtime::{Body, Error, Request, Response, StatusCode, run};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    run(handler).await
+}
+
+#[derive(Debug, Deserialize)]
+struct ActionRequest {
+    match_id: String,
+    /// Index the action will have in the match, guards against lost or doubled requests.
+    seq: usize,
+    action: GameAction,
+}
+
+pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
+    if req.method() != "POST" {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "use POST");
+    }
+
+    let username = match authenticate(&req) {
+        Ok(username) => username,
+        Err(resp) => return Ok(resp),
+    };
+    let body: ActionRequest = match json_body(&req) {
+        Ok(body) => body,
+        Err(resp) => return Ok(resp),
+    };
+
+    let match_id = body.match_id.trim().to_ascii_uppercase();
+    let matches = matches().await?;
+    let Some(mut game) = matches.find_one(doc! { "_id": &match_id }).await? else {
+        return error_response(StatusCode::NOT_FOUND, "no match with this code");
+    };
+    let Some(side) = game.side_of(&username) else {
+        return error_response(StatusCode::FORBIDDEN, "not a player of this match");
+    };
+
+    let seq = game.actions.len();
+    if body.seq != seq {
+        return json_response(
+            StatusCode::CONFLICT,
+            json!({ "error": "out of sync", "action_count": seq }),
+        );
+    }
+
+    if let Err(msg) = game.apply(&card_collection(), side, body.action) {
+        return error_response(StatusCode::UNPROCESSABLE_ENTITY, &msg);
+    }
+
+    // the filter on the action count makes concurrent writers lose instead of interleave
+    let result = matches
+        .replace_one(
+            doc! { "_id": &game.id, "actions": { "$size": seq as i64 } },
+            &game,
+        )
+        .await?;
+    if result.matched_count == 0 {
+        return error_response(StatusCode::CONFLICT, "out of sync");
+    }
+
+    json_response(
+        StatusCode::OK,
+        json!({ "action_count": game.actions.len() }),
+    )
+}
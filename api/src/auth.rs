@@ -0,0 +1,212 @@
+//! Player accounts: salted password hashes and signed session tokens.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use vercel_runtime::{Body, Request, Response, StatusCode};
+
+use crate::error_response;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// PBKDF2 rounds per password hash.
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Bytes of random salt stored with each account.
+const SALT_LEN: usize = 16;
+
+/// How long a session token stays valid, in seconds.
+pub const SESSION_LIFETIME: i64 = 30 * 24 * 60 * 60;
+
+pub const MIN_USERNAME_LEN: usize = 3;
+pub const MAX_USERNAME_LEN: usize = 20;
+pub const MIN_PASSWORD_LEN: usize = 8;
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+/// Shortest `SESSION_SECRET` accepted, in bytes. Anything shorter could be
+/// guessed and every token forged with it.
+pub const MIN_SESSION_SECRET_LEN: usize = 32;
+
+/// One player in the `accounts` collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Lowercase, so names differing only in case can't both exist.
+    #[serde(rename = "_id")]
+    pub username: String,
+    /// Hex encoded.
+    pub salt: String,
+    /// Hex encoded PBKDF2-HMAC-SHA256 of the password and salt.
+    pub password_hash: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+impl Account {
+    /// New account with a fresh salt. Both arguments must already be validated.
+    pub fn new(username: &str, password: &str) -> Self {
+        let salt: [u8; SALT_LEN] = rand::random();
+        Self {
+            username: username.to_string(),
+            salt: hex::encode(salt),
+            password_hash: hex::encode(hash_password(password, &salt)),
+            created_at: mongodb::bson::DateTime::now().timestamp_millis(),
+        }
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        let (Ok(salt), Ok(stored)) = (hex::decode(&self.salt), hex::decode(&self.password_hash))
+        else {
+            return false;
+        };
+        hash_password(password, &salt).ct_eq(&stored).into()
+    }
+}
+
+fn hash_password(password: &str, salt: &[u8]) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, PBKDF2_ROUNDS)
+}
+
+/// Normalized form of a username typed by a player.
+pub fn validate_username(username: &str) -> Result<String, String> {
+    let username = username.trim().to_ascii_lowercase();
+    let len = username.chars().count();
+    if !(MIN_USERNAME_LEN..=MAX_USERNAME_LEN).contains(&len)
+        || !username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!(
+            "username must be {} to {} letters, digits or '_'",
+            MIN_USERNAME_LEN, MAX_USERNAME_LEN
+        ));
+    }
+    Ok(username)
+}
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        return Err(format!(
+            "password must be {} to {} characters",
+            MIN_PASSWORD_LEN, MAX_PASSWORD_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Key session tokens are signed with, from `SESSION_SECRET`. Missing or
+/// too short, sessions are not configured.
+pub fn session_secret() -> Result<Vec<u8>, String> {
+    let secret = std::env::var("SESSION_SECRET").map_err(|_| "SESSION_SECRET is not set")?;
+    validate_session_secret(secret)
+}
+
+fn validate_session_secret(secret: String) -> Result<Vec<u8>, String> {
+    if secret.trim().len() < MIN_SESSION_SECRET_LEN {
+        return Err(format!(
+            "SESSION_SECRET must be at least {} bytes",
+            MIN_SESSION_SECRET_LEN
+        ));
+    }
+    Ok(secret.into_bytes())
+}
+
+fn sign(secret: &[u8], payload: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Token proving the holder logged in as `username`, valid until
+/// `now + SESSION_LIFETIME`. Written as `<username>.<expiry>.<signature>`.
+pub fn issue_token(secret: &[u8], username: &str, now: i64) -> String {
+    let payload = format!("{}.{}", username, now + SESSION_LIFETIME);
+    let signature = hex::encode(sign(secret, &payload).finalize().into_bytes());
+    format!("{}.{}", payload, signature)
+}
+
+/// Username a token was issued for, if it is genuine and not expired at `now`.
+pub fn verify_token(secret: &[u8], token: &str, now: i64) -> Option<String> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let (username, expiry) = payload.split_once('.')?;
+
+    sign(secret, payload)
+        .verify_slice(&hex::decode(signature).ok()?)
+        .ok()?;
+    if expiry.parse::<i64>().ok()? < now {
+        return None;
+    }
+    Some(username.to_string())
+}
+
+/// Seconds since the Unix epoch.
+pub fn now_secs() -> i64 {
+    mongodb::bson::DateTime::now().timestamp_millis() / 1000
+}
+
+/// Username of the session in the `Authorization: Bearer <token>` header,
+/// or the 401 reply to send back.
+#[allow(clippy::result_large_err)]
+pub fn authenticate(req: &Request) -> Result<String, Response<Body>> {
+    let reply =
+        |status, msg: &str| error_response(status, msg).expect("static response is always valid");
+
+    let Ok(secret) = session_secret() else {
+        return Err(reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "sessions are not configured",
+        ));
+    };
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    match token.and_then(|t| verify_token(&secret, t.trim(), now_secs())) {
+        Some(username) => Ok(username),
+        None => Err(reply(StatusCode::UNAUTHORIZED, "log in first")),
+    }
+}
+
+#[test]
+fn password_hash_test() {
+    let account = Account::new("ann", "correct horse");
+    assert!(account.check_password("correct horse"));
+    assert!(!account.check_password("correct horsf"));
+    assert_ne!(
+        Account::new("bob", "correct horse").password_hash,
+        account.password_hash
+    );
+}
+
+#[test]
+fn session_token_test() {
+    let token = issue_token(b"secret", "ann", 1000);
+    assert_eq!(
+        verify_token(b"secret", &token, 1000),
+        Some("ann".to_string())
+    );
+    assert_eq!(verify_token(b"other", &token, 1000), None);
+    assert_eq!(
+        verify_token(b"secret", &token, 1001 + SESSION_LIFETIME),
+        None
+    );
+
+    let forged = token.replacen("ann", "bob", 1);
+    assert_eq!(verify_token(b"secret", &forged, 1000), None);
+
+    assert_eq!(validate_username(" Ann_1 "), Ok("ann_1".to_string()));
+    assert!(validate_username("a b").is_err());
+    assert!(validate_password("short").is_err());
+
+    assert!(validate_session_secret(String::new()).is_err());
+    assert!(validate_session_secret(" ".repeat(40)).is_err());
+    assert!(validate_session_secret("secret".to_string()).is_err());
+    let secret = "k".repeat(MIN_SESSION_SECRET_LEN);
+    assert_eq!(
+        validate_session_secret(secret.clone()),
+        Ok(secret.into_bytes())
+    );
+}
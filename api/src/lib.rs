@@ -0,0 +1,460 @@
+//! Code shared by the API handlers: the database connection, the match and
+//! chat documents, and request/response helpers.
+
+pub mod auth;
+
+use mongodb::bson::{Document, doc, oid::ObjectId};
+use mongodb::{Client, Collection, IndexModel};
+use opinion_rules::{
+    CardCollection, GameAction, GameState, Opening, Phase, RULES_VERSION, Replay, Tern,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::json;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use vercel_runtime::{Body, Error, Request, Response, StatusCode};
+
+/// Connect to the `opinion` database at `MONGODB_URI`. A deployment without
+/// it fails every request instead of quietly talking to a database that
+/// isn't there, handler tests point it at a local server themselves.
+pub async fn database() -> Result<mongodb::Database, Error> {
+    let uri = std::env::var("MONGODB_URI").map_err(|_| "MONGODB_URI is not set")?;
+
+    let client = Client::with_uri_str(uri).await?;
+    Ok(client.database("opinion"))
+}
+
+pub async fn matches() -> Result<Collection<MatchDoc>, Error> {
+    Ok(database().await?.collection("matches"))
+}
+
+pub async fn accounts() -> Result<Collection<auth::Account>, Error> {
+    Ok(database().await?.collection("accounts"))
+}
+
+pub async fn chats() -> Result<Collection<ChatMessage>, Error> {
+    Ok(database().await?.collection("chat"))
+}
+
+/// Set once this instance made sure the chat history index exists.
+static CHAT_INDEX_READY: AtomicBool = AtomicBool::new(false);
+
+/// Create the index `api-getmsg` pages through, once per warm instance.
+pub async fn ensure_chat_index(chats: &Collection<ChatMessage>) -> Result<(), Error> {
+    if CHAT_INDEX_READY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let index = IndexModel::builder()
+        .keys(doc! { "room": 1, "sent_at": 1, "_id": 1 })
+        .build();
+    chats.create_index(index).await?;
+    CHAT_INDEX_READY.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Whether an insert failed because the `_id` is already taken.
+pub fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(
+        *e.kind,
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(ref w)) if w.code == 11000
+    )
+}
+
+/// Reply with `body` serialized as JSON.
+pub fn json_response(status: StatusCode, body: serde_json::Value) -> Result<Response<Body>, Error> {
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(body.to_string().into())?)
+}
+
+/// Reply with `{"error": msg}`.
+pub fn error_response(status: StatusCode, msg: &str) -> Result<Response<Body>, Error> {
+    json_response(status, json!({ "error": msg }))
+}
+
+/// Parse the JSON request body, or build the 400 reply explaining why it could not be.
+#[allow(clippy::result_large_err)]
+pub fn json_body<T: DeserializeOwned>(req: &Request) -> Result<T, Response<Body>> {
+    let parsed = match req.body() {
+        Body::Text(text) => serde_json::from_str(text),
+        Body::Binary(bin) => serde_json::from_slice(bin),
+        Body::Empty => serde_json::from_str(""),
+    };
+
+    parsed.map_err(|e| {
+        error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("invalid request body: {}", e),
+        )
+        .expect("static response is always valid")
+    })
+}
+
+/// Value of the query parameter `name`, percent-decoded.
+pub fn query_param(req: &Request, name: &str) -> Option<String> {
+    req.uri()
+        .query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Random string of `len` characters from `[A-Z0-9]`, without look-alikes.
+pub fn random_code(len: usize) -> String {
+    use rand::Rng;
+    const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+    let mut rng = rand::rng();
+    (0..len)
+        .map(|_| ALPHABET[rng.random_range(0..ALPHABET.len())] as char)
+        .collect()
+}
+
+/// The card definitions the game client ships with, so the server deals the same decks.
+pub fn card_collection() -> CardCollection {
+    CardCollection::parse("cards.json", include_str!("../../public/assets/cards.json"))
+        .expect("bundled card definitions are valid")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStatus {
+    /// Created, waiting for a second player to join.
+    Waiting,
+    Playing,
+}
+
+/// Why a match stored by an older deployment is refused.
+pub const OUTDATED_MATCH: &str = "match was made with older rules, start a new one";
+
+/// One online match in the `matches` collection. Clients rebuild the game
+/// from `seed` and `actions`, exactly like a replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchDoc {
+    /// Short code players type to join.
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// `RULES_VERSION` the match is dealt with, matches of another version
+    /// can't be played on.
+    #[serde(default = "MatchDoc::first_version")]
+    pub rules_version: u32,
+    /// Stored signed because BSON has no unsigned 64-bit integer.
+    pub seed: i64,
+    /// Username of each player, player1 first.
+    pub players: Vec<String>,
+    pub status: MatchStatus,
+    pub actions: Vec<GameAction>,
+    /// Whose turn it is after `actions`.
+    pub turn: Tern,
+    pub phase: Phase,
+}
+
+impl MatchDoc {
+    pub fn new(id: String, seed: u64, host: &str) -> Self {
+        Self {
+            id,
+            rules_version: RULES_VERSION,
+            seed: seed as i64,
+            players: vec![host.to_string()],
+            status: MatchStatus::Waiting,
+            actions: Vec::new(),
+            turn: Tern::Player1,
+            phase: Phase::Main,
+        }
+    }
+
+    fn first_version() -> u32 {
+        1
+    }
+
+    /// Whether this build can still play the match, see `RULES_VERSION`.
+    pub fn is_current(&self) -> bool {
+        self.rules_version == RULES_VERSION
+    }
+
+    /// Which side `username` plays.
+    pub fn side_of(&self, username: &str) -> Option<Tern> {
+        match self.players.iter().position(|p| p == username)? {
+            0 => Some(Tern::Player1),
+            _ => Some(Tern::Player2),
+        }
+    }
+
+    /// Replay the match with the shared rules and check `action` against
+    /// it, the same way each client does before it shows the action.
+    pub fn apply(
+        &mut self,
+        col: &CardCollection,
+        side: Tern,
+        action: GameAction,
+    ) -> Result<(), String> {
+        if !self.is_current() {
+            return Err(OUTDATED_MATCH.to_string());
+        }
+        if self.status != MatchStatus::Playing {
+            return Err("match has not started".to_string());
+        }
+        if action.player() != side {
+            return Err("action is for the other player".to_string());
+        }
+
+        // online matches are played with random libraries and the classic opening
+        let replay = Replay {
+            rules_version: self.rules_version,
+            seed: self.seed as u64,
+            actions: self.actions.clone(),
+            decks: [None, None],
+            opening: Opening::default(),
+        };
+        let mut state = GameState::from_replay(col, &replay)
+            .map_err(|e| format!("stored match no longer replays: {}", e))?;
+        state.apply(action).map_err(|e| e.to_string())?;
+
+        self.actions.push(action);
+        self.turn = state.turn;
+        self.phase = state.phase;
+        Ok(())
+    }
+
+    /// What anyone with the match code may see of it. The seed is left out,
+    /// with it every card still to be drawn could be worked out. The players
+    /// get it from `api-creatematch` and `api-joinmatch`.
+    pub fn public_view(&self, since: usize) -> serde_json::Value {
+        json!({
+            "match_id": self.id,
+            "players": self.players,
+            "status": self.status,
+            "turn": self.turn,
+            "phase": self.phase,
+            "action_count": self.actions.len(),
+            "since": since,
+            "actions": self.actions.get(since..).unwrap_or_default(),
+        })
+    }
+}
+
+/// Longest sender name accepted by `api-postmsg`, in characters.
+pub const MAX_SENDER_LEN: usize = 32;
+
+/// Longest chat message accepted by `api-postmsg`, in characters.
+pub const MAX_MESSAGE_LEN: usize = 500;
+
+/// Longest room id, match codes are much shorter.
+pub const MAX_ROOM_LEN: usize = 32;
+
+/// Room of messages posted without one.
+pub const DEFAULT_ROOM: &str = "lobby";
+
+/// One line in the `chat` collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub sender: String,
+    pub message: String,
+    /// Match code the message was sent in, or a free room name like `lobby`.
+    #[serde(default)]
+    pub room: String,
+    /// Milliseconds since the Unix epoch, set by the server.
+    #[serde(default)]
+    pub sent_at: i64,
+}
+
+impl ChatMessage {
+    /// Check what a client sent and stamp it with the current time.
+    pub fn new(sender: &str, message: &str, room: Option<&str>) -> Result<Self, String> {
+        let sender = sender.trim();
+        let message = message.trim();
+        let room = room.map(str::trim).unwrap_or(DEFAULT_ROOM);
+
+        if sender.is_empty() {
+            return Err("sender must not be empty".to_string());
+        }
+        if sender.chars().count() > MAX_SENDER_LEN {
+            return Err(format!(
+                "sender is longer than {} characters",
+                MAX_SENDER_LEN
+            ));
+        }
+        if message.is_empty() {
+            return Err("message must not be empty".to_string());
+        }
+        if message.chars().count() > MAX_MESSAGE_LEN {
+            return Err(format!(
+                "message is longer than {} characters",
+                MAX_MESSAGE_LEN
+            ));
+        }
+        if room.is_empty()
+            || room.len() > MAX_ROOM_LEN
+            || !room
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "room must be 1 to {} letters, digits, '-' or '_'",
+                MAX_ROOM_LEN
+            ));
+        }
+
+        Ok(Self {
+            id: None,
+            sender: sender.to_string(),
+            message: message.to_string(),
+            room: room.to_string(),
+            sent_at: mongodb::bson::DateTime::now().timestamp_millis(),
+        })
+    }
+
+    /// The message as clients see it, with the id as a plain string.
+    pub fn public_view(&self) -> serde_json::Value {
+        json!({
+            "id": self.id.map(|id| id.to_hex()),
+            "sender": self.sender,
+            "message": self.message,
+            "room": self.room,
+            "sent_at": self.sent_at,
+        })
+    }
+}
+
+/// Position in the chat history, that of one message. Pages start right
+/// after it or end right before it. Written as `<sent_at>-<id>` so clients
+/// can pass it back as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatCursor {
+    pub sent_at: i64,
+    pub id: ObjectId,
+}
+
+impl ChatCursor {
+    /// Cursor at `msg`, `None` if it was never stored.
+    pub fn at(msg: &ChatMessage) -> Option<Self> {
+        Some(Self {
+            sent_at: msg.sent_at,
+            id: msg.id?,
+        })
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let (sent_at, id) = text.split_once('-')?;
+        Some(Self {
+            sent_at: sent_at.parse().ok()?,
+            id: ObjectId::parse_str(id).ok()?,
+        })
+    }
+
+    /// Query matching the messages that come after the cursor, in
+    /// `sent_at`, `_id` order.
+    pub fn filter_after(&self) -> Document {
+        doc! {
+            "$or": [
+                { "sent_at": { "$gt": self.sent_at } },
+                { "sent_at": self.sent_at, "_id": { "$gt": self.id } },
+            ]
+        }
+    }
+
+    /// Query matching the messages that come before the cursor.
+    pub fn filter_before(&self) -> Document {
+        doc! {
+            "$or": [
+                { "sent_at": { "$lt": self.sent_at } },
+                { "sent_at": self.sent_at, "_id": { "$lt": self.id } },
+            ]
+        }
+    }
+}
+
+impl fmt::Display for ChatCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.sent_at, self.id.to_hex())
+    }
+}
+
+#[test]
+fn match_turn_order_test() {
+    let col = card_collection();
+    let mut game = MatchDoc::new("ABCDEF".to_string(), 7, "host");
+    let end = |player| GameAction::EndPhase { player };
+
+    assert!(game.apply(&col, Tern::Player1, end(Tern::Player1)).is_err());
+    game.players.push("guest".to_string());
+    game.status = MatchStatus::Playing;
+    assert_eq!(game.side_of("guest"), Some(Tern::Player2));
+
+    assert!(game.apply(&col, Tern::Player2, end(Tern::Player2)).is_err());
+    assert!(game.apply(&col, Tern::Player2, end(Tern::Player1)).is_err());
+    let missing_card = GameAction::PlayCard {
+        player: Tern::Player1,
+        hand_index: 99,
+    };
+    assert!(game.apply(&col, Tern::Player1, missing_card).is_err());
+    assert!(game.apply(&col, Tern::Player1, end(Tern::Player1)).is_ok());
+    assert_eq!(game.phase, Phase::Combat);
+    assert!(game.apply(&col, Tern::Player1, end(Tern::Player1)).is_ok());
+    assert_eq!((game.turn, game.phase), (Tern::Player2, Phase::Main));
+    assert_eq!(game.actions.len(), 2);
+    assert!(game.public_view(0).get("seed").is_none());
+
+    game.rules_version -= 1;
+    assert_eq!(
+        game.apply(&col, Tern::Player2, end(Tern::Player2)),
+        Err(OUTDATED_MATCH.to_string())
+    );
+}
+
+#[test]
+fn chat_message_validation_test() {
+    let msg = ChatMessage::new(" ann ", " hi there ", None).unwrap();
+    assert_eq!(
+        (msg.sender.as_str(), msg.message.as_str()),
+        ("ann", "hi there")
+    );
+    assert_eq!(msg.room, DEFAULT_ROOM);
+    assert!(msg.sent_at > 0);
+
+    assert!(ChatMessage::new("  ", "hi", None).is_err());
+    assert!(ChatMessage::new("ann", "", None).is_err());
+    assert!(ChatMessage::new("ann", &"x".repeat(MAX_MESSAGE_LEN + 1), None).is_err());
+    assert!(ChatMessage::new("ann", "hi", Some("no spaces")).is_err());
+    assert!(ChatMessage::new("ann", "hi", Some("ABC234")).is_ok());
+}
+
+#[test]
+fn chat_cursor_test() {
+    let mut msg = ChatMessage::new("ann", "hi", None).unwrap();
+    assert_eq!(ChatCursor::at(&msg), None);
+
+    msg.id = Some(ObjectId::new());
+    let cursor = ChatCursor::at(&msg).unwrap();
+    assert_eq!(ChatCursor::parse(&cursor.to_string()), Some(cursor));
+    assert_eq!(ChatCursor::parse("12-nothex"), None);
+    assert_eq!(ChatCursor::parse("12"), None);
+}
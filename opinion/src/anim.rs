@@ -0,0 +1,370 @@
+//! Card animations: easing curves, tweens between rects, and a queue that
+//! shows what the rules did one step after another.
+
+use macroquad::prelude as mcp;
+use opinion_rules::{Card, GameEvent, Tern};
+use std::collections::VecDeque;
+
+use crate::piles::Stack;
+use crate::{CardLayout, Pile, PlayerView, Renderer};
+
+/// Seconds a card takes from the library to the hand.
+const DRAW_TIME: f32 = 0.25;
+/// Seconds a card takes from the hand to the arena or graveyard.
+const PLAY_TIME: f32 = 0.4;
+/// Seconds an attacker takes to strike and come back.
+const ATTACK_TIME: f32 = 0.45;
+/// Seconds a card takes from the arena or hand to the graveyard.
+const DISCARD_TIME: f32 = 0.35;
+/// Seconds a card takes from the hand back to the library.
+const RETURN_TIME: f32 = 0.3;
+const SHUFFLE_TIME: f32 = 0.4;
+/// Seconds resting cards take to move over when their row changes.
+pub const SLIDE_TIME: f32 = 0.15;
+/// Seconds a pile takes to settle after a card landed on it.
+pub const LAND_TIME: f32 = 0.25;
+
+/// How far an attacker travels towards its target, as a share of the distance.
+const LUNGE_REACH: f32 = 0.7;
+
+/// Maps linear progress to eased progress, both running from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ease {
+    Linear,
+    InQuad,
+    OutQuad,
+    InOutCubic,
+    /// Overshoots the target a little before settling on it.
+    OutBack,
+    /// Out to the target and back to the start, for attacks.
+    Lunge,
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+            Ease::InQuad => t * t,
+            Ease::OutQuad => 1.0 - (1.0 - t) * (1.0 - t),
+            Ease::InOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+            Ease::OutBack => {
+                const OVERSHOOT: f32 = 1.70158;
+                1.0 + (OVERSHOOT + 1.0) * (t - 1.0).powi(3) + OVERSHOOT * (t - 1.0).powi(2)
+            }
+            Ease::Lunge => {
+                const STRIKE: f32 = 0.35;
+                if t < STRIKE {
+                    Ease::OutQuad.apply(t / STRIKE)
+                } else {
+                    1.0 - Ease::InOutCubic.apply((t - STRIKE) / (1.0 - STRIKE))
+                }
+            }
+        }
+    }
+}
+
+/// A rect moving from `from` to `to` over `duration` seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Tween {
+    pub from: mcp::Rect,
+    pub to: mcp::Rect,
+    pub duration: f32,
+    pub ease: Ease,
+    elapsed: f32,
+}
+
+impl Tween {
+    pub fn new(from: mcp::Rect, to: mcp::Rect, duration: f32, ease: Ease) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            ease,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+
+    /// Linear progress from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            1.0
+        } else {
+            self.elapsed / self.duration
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Where the rect is right now.
+    pub fn rect(&self) -> mcp::Rect {
+        let t = self.ease.apply(self.progress());
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        mcp::Rect::new(
+            lerp(self.from.x, self.to.x),
+            lerp(self.from.y, self.to.y),
+            lerp(self.from.w, self.to.w),
+            lerp(self.from.h, self.to.h),
+        )
+    }
+}
+
+/// Where a flying card lands once its tween is done.
+enum Landing {
+    Library,
+    Hand,
+    Arena,
+    Graveyard,
+}
+
+/// What the running animation does with its tween.
+enum Motion {
+    /// A card between two piles. It is in neither while flying, so the
+    /// animator draws it.
+    Fly {
+        card: Card,
+        face_up: bool,
+        to: Landing,
+    },
+    /// An arena card striking and coming back to its slot.
+    Lunge { arena_index: usize },
+    /// The library being shuffled.
+    Shuffle,
+}
+
+struct Running {
+    side: Tern,
+    motion: Motion,
+    tween: Tween,
+}
+
+/// Plays `GameEvent`s on the `PlayerView`s one after another. The views
+/// show the piles as they were before the events, each animation moves
+/// its cards over when it starts and lands them when it is done.
+#[derive(Default)]
+pub struct Animator {
+    queue: VecDeque<GameEvent>,
+    running: Option<Running>,
+}
+
+impl Animator {
+    pub fn push(&mut self, events: &[GameEvent]) {
+        self.queue.extend(events.iter().cloned());
+    }
+
+    /// Whether anything is still moving or waiting to move.
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some() || !self.queue.is_empty()
+    }
+
+    /// Advance the running animation by `dt` seconds, starting the next one
+    /// as soon as it is done. `face_up` tells for each player whether cards
+    /// drawn into their hand are shown. Returns true on the frame the last
+    /// animation finished.
+    pub fn update(&mut self, dt: f32, mut views: [&mut PlayerView; 2], face_up: [bool; 2]) -> bool {
+        if !self.is_busy() {
+            return false;
+        }
+
+        if let Some(running) = &mut self.running {
+            running.tween.advance(dt);
+            if let Motion::Lunge { arena_index } = running.motion
+                && let Some(rect) = views[index(running.side)].arena_rects.get_mut(arena_index)
+            {
+                *rect = running.tween.rect();
+            }
+            if !running.tween.is_done() {
+                return false;
+            }
+            if let Some(running) = self.running.take() {
+                Animator::finish(running, &mut views);
+            }
+        }
+
+        // starts right away, so a landed card is never missing for a frame
+        while self.running.is_none() {
+            let Some(event) = self.queue.pop_front() else {
+                return true;
+            };
+            self.running = Animator::start(event, &mut views, face_up);
+        }
+        false
+    }
+
+    /// Take the cards of `event` out of their pile and set them moving.
+    /// `None` if the views don't have the cards the event talks about.
+    fn start(
+        event: GameEvent,
+        views: &mut [&mut PlayerView; 2],
+        face_up: [bool; 2],
+    ) -> Option<Running> {
+        let fly = |card, face_up, to| Motion::Fly { card, face_up, to };
+
+        let (side, motion, tween) = match event {
+            GameEvent::Shuffled { player } => {
+                let rect = views[index(player)].library_rect;
+                let tween = Tween::new(rect, rect, SHUFFLE_TIME, Ease::Linear);
+                (player, Motion::Shuffle, tween)
+            }
+            GameEvent::Drew { player, card } => {
+                let view = &mut views[index(player)];
+                view.shown.library.pop();
+                let count = view.shown.hand.len();
+                let to = view.slot_rect(Pile::Hand, count, count + 1);
+                let tween = Tween::new(view.library_rect, to, DRAW_TIME, Ease::OutQuad);
+                let motion = fly(card, face_up[index(player)], Landing::Hand);
+                (player, motion, tween)
+            }
+            GameEvent::Played {
+                player,
+                hand_index,
+                card,
+            } => {
+                let view = &mut views[index(player)];
+                let from = view.take(Pile::Hand, hand_index)?;
+                let (to, landing) = if card.card_type.is_permanent() {
+                    let count = view.shown.arena.len();
+                    (
+                        view.slot_rect(Pile::Arena, count, count + 1),
+                        Landing::Arena,
+                    )
+                } else {
+                    (view.graveyard_rect, Landing::Graveyard)
+                };
+                let tween = Tween::new(from, to, PLAY_TIME, Ease::InOutCubic);
+                (player, fly(card, true, landing), tween)
+            }
+            GameEvent::Attacked {
+                player,
+                attacker,
+                blocker,
+            } => {
+                let from = *views[index(player)].arena_rects.get(attacker)?;
+                let defender = &views[index(player.other())];
+                let target = blocker
+                    .and_then(|ind| defender.arena_rects.get(ind).copied())
+                    .unwrap_or(defender.hand_rect);
+                let to = from.offset((target.center() - from.center()) * LUNGE_REACH);
+                let tween = Tween::new(from, to, ATTACK_TIME, Ease::Lunge);
+                let motion = Motion::Lunge {
+                    arena_index: attacker,
+                };
+                (player, motion, tween)
+            }
+            GameEvent::Destroyed {
+                player,
+                arena_index,
+                card,
+            } => {
+                let view = &mut views[index(player)];
+                let from = view.take(Pile::Arena, arena_index)?;
+                let tween = Tween::new(from, view.graveyard_rect, DISCARD_TIME, Ease::InQuad);
+                (player, fly(card, true, Landing::Graveyard), tween)
+            }
+            GameEvent::Discarded {
+                player,
+                hand_index,
+                card,
+            } => {
+                let view = &mut views[index(player)];
+                let from = view.take(Pile::Hand, hand_index)?;
+                let tween = Tween::new(from, view.graveyard_rect, DISCARD_TIME, Ease::InQuad);
+                (player, fly(card, true, Landing::Graveyard), tween)
+            }
+            GameEvent::Returned {
+                player,
+                hand_index,
+                card,
+            } => {
+                let view = &mut views[index(player)];
+                let from = view.take(Pile::Hand, hand_index)?;
+                let tween = Tween::new(from, view.library_rect, RETURN_TIME, Ease::InOutCubic);
+                let motion = fly(card, face_up[index(player)], Landing::Library);
+                (player, motion, tween)
+            }
+        };
+
+        Some(Running {
+            side,
+            motion,
+            tween,
+        })
+    }
+
+    /// Put the cards of a finished animation where they belong.
+    fn finish(running: Running, views: &mut [&mut PlayerView; 2]) {
+        let view = &mut views[index(running.side)];
+        match running.motion {
+            Motion::Fly { card, to, .. } => {
+                match to {
+                    Landing::Library => {
+                        view.shown.library.insert(0, card);
+                        view.bump(Stack::Library);
+                    }
+                    Landing::Hand => view.shown.hand.push(card),
+                    Landing::Arena => view.shown.arena.push(card),
+                    Landing::Graveyard => {
+                        view.shown.graveyard.push(card);
+                        view.bump(Stack::Graveyard);
+                    }
+                }
+                view.update_card_position();
+            }
+            Motion::Lunge { arena_index } => {
+                if let Some(rect) = view.arena_rects.get_mut(arena_index) {
+                    *rect = running.tween.from;
+                }
+            }
+            Motion::Shuffle => {}
+        }
+    }
+
+    /// Draw the cards that are between piles, above everything else.
+    pub fn render(&self, font: &mcp::Font) {
+        let Some(running) = &self.running else {
+            return;
+        };
+
+        match &running.motion {
+            Motion::Fly { card, face_up, .. } => {
+                let rect = running.tween.rect();
+                if *face_up {
+                    Renderer::render_card(card, rect, CardLayout::Minimal, font);
+                } else {
+                    Renderer::render_card_back(rect);
+                }
+            }
+            Motion::Shuffle => {
+                let rect = running.tween.from;
+                let t = running.tween.progress();
+                // a few cards slip out to either side and back in
+                for k in 0..3 {
+                    let phase = t * std::f32::consts::TAU * 2.0 + k as f32 * 2.1;
+                    let dx = phase.sin() * rect.w * 0.35 * (1.0 - t);
+                    let dy = -(k as f32) * 2.0;
+                    Renderer::render_card_back(rect.offset(mcp::vec2(dx, dy)));
+                }
+            }
+            Motion::Lunge { .. } => {}
+        }
+    }
+}
+
+fn index(side: Tern) -> usize {
+    match side {
+        Tern::Player1 => 0,
+        Tern::Player2 => 1,
+    }
+}
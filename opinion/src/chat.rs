@@ -0,0 +1,256 @@
+//! Chat overlay shown over a match, talking to `api-getmsg` and `api-postmsg`.
+
+use macroquad::prelude as mcp;
+use serde::Deserialize;
+
+use crate::net::{HttpRequest, HttpResponse, Session};
+use crate::{SizeRatio, fit_line};
+
+/// Seconds between two polls for new messages.
+const CHAT_POLL_INTERVAL: f64 = 2.0;
+
+/// Messages kept on screen, older ones are dropped.
+const CHAT_HISTORY: usize = 50;
+
+/// Longest message `api-postmsg` accepts, in characters.
+const CHAT_MAX_LEN: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct ChatLine {
+    sender: String,
+    message: String,
+}
+
+/// Reply of `/api/getmsg`.
+#[derive(Debug, Deserialize)]
+struct ChatPage {
+    data: Vec<ChatLine>,
+    next_cursor: Option<String>,
+}
+
+pub struct ChatPanel {
+    /// Match code, or `lobby` for games without one.
+    room: String,
+    /// Messages are posted as this player, reading works without one.
+    session: Option<Session>,
+    open: bool,
+    input: String,
+    messages: Vec<ChatLine>,
+    /// Messages received while the panel was closed.
+    unread: usize,
+    /// Where the next poll picks up, `None` until the first page arrived.
+    cursor: Option<String>,
+    poll_req: Option<HttpRequest>,
+    send_req: Option<HttpRequest>,
+    last_poll: f64,
+    /// Last problem talking to the server, shown in the panel.
+    error: Option<String>,
+}
+
+impl ChatPanel {
+    pub fn new(room: &str, session: Option<Session>) -> Self {
+        Self {
+            room: room.to_string(),
+            session,
+            open: false,
+            input: String::new(),
+            messages: Vec::new(),
+            unread: 0,
+            cursor: None,
+            poll_req: None,
+            send_req: None,
+            last_poll: f64::MIN,
+            error: None,
+        }
+    }
+
+    /// Whether key presses are going into the input box instead of the game.
+    pub fn is_typing(&self) -> bool {
+        self.open
+    }
+
+    pub fn update(&mut self) {
+        if mcp::is_key_pressed(mcp::KeyCode::Tab) {
+            self.open = !self.open;
+            self.unread = 0;
+        }
+        if self.open && mcp::is_key_pressed(mcp::KeyCode::Escape) {
+            self.open = false;
+        }
+
+        // always drain typed characters, or they pile up until the panel opens
+        while let Some(ch) = mcp::get_char_pressed() {
+            if self.open
+                && self.session.is_some()
+                && !ch.is_control()
+                && self.input.chars().count() < CHAT_MAX_LEN
+            {
+                self.input.push(ch);
+            }
+        }
+        if self.open {
+            if mcp::is_key_pressed(mcp::KeyCode::Backspace) {
+                self.input.pop();
+            }
+            if mcp::is_key_pressed(mcp::KeyCode::Enter) {
+                self.send();
+            }
+        }
+
+        self.update_send();
+        self.update_poll();
+    }
+
+    fn send(&mut self) {
+        let message = self.input.trim();
+        if message.is_empty() || self.send_req.is_some() {
+            return;
+        }
+        let Some(session) = &self.session else {
+            return;
+        };
+
+        // the server takes the sender from the session
+        let body = serde_json::json!({
+            "message": message,
+            "room": self.room,
+        });
+        self.send_req = Some(HttpRequest::post_as(session, "/api/postmsg", &body));
+        self.input.clear();
+    }
+
+    fn update_send(&mut self) {
+        let Some(result) = self.send_req.as_mut().and_then(|r| r.poll()) else {
+            return;
+        };
+        self.send_req = None;
+
+        match result {
+            Ok(resp) if resp.is_success() => {
+                self.error = None;
+                // fetch it back right away instead of waiting for the next poll
+                self.last_poll = f64::MIN;
+            }
+            Ok(resp) => self.error = Some(resp.error_message()),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn update_poll(&mut self) {
+        if let Some(req) = &mut self.poll_req {
+            match req.poll() {
+                None => return,
+                Some(result) => self.receive(result),
+            }
+            self.poll_req = None;
+        }
+
+        let now = mcp::get_time();
+        if now - self.last_poll < CHAT_POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = now;
+
+        let mut path = format!("/api/getmsg?room={}&limit={}", self.room, CHAT_HISTORY);
+        if let Some(cursor) = &self.cursor {
+            path.push_str(&format!("&cursor={}", cursor));
+        }
+        self.poll_req = Some(HttpRequest::get(&path));
+    }
+
+    fn receive(&mut self, result: Result<HttpResponse, String>) {
+        let page = match result {
+            Ok(resp) if resp.is_success() => serde_json::from_str::<ChatPage>(&resp.body)
+                .map_err(|e| format!("bad chat reply: {}", e)),
+            Ok(resp) => Err(resp.error_message()),
+            Err(e) => Err(e),
+        };
+
+        match page {
+            Ok(page) => {
+                self.error = None;
+                if !self.open {
+                    self.unread += page.data.len();
+                }
+                self.messages.extend(page.data);
+                let dropped = self.messages.len().saturating_sub(CHAT_HISTORY);
+                self.messages.drain(..dropped);
+                if page.next_cursor.is_some() {
+                    self.cursor = page.next_cursor;
+                }
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    pub fn render(&self, font: &mcp::Font) {
+        let font_size = SizeRatio::get_y(0.03);
+        let params = |color| mcp::TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color,
+            ..Default::default()
+        };
+
+        if !self.open {
+            let hint = match self.unread {
+                0 => "Tab - Chat".to_string(),
+                n => format!("Tab - Chat ({} new)", n),
+            };
+            let (x, y) = SizeRatio::get(0.82, 0.99);
+            mcp::draw_text_ex(&hint, x, y, params(mcp::LIGHTGRAY));
+            return;
+        }
+
+        let panel = SizeRatio::new(0.55, 0.05, 0.43, 0.6);
+        mcp::draw_rectangle(
+            panel.x,
+            panel.y,
+            panel.w,
+            panel.h,
+            mcp::Color::new(0.0, 0.0, 0.0, 0.8),
+        );
+
+        let padding = font_size / 2.0;
+        let line_height = font_size * 1.2;
+        let width = panel.w - padding * 2.0;
+
+        // input box along the bottom, messages stacked upwards from it
+        let mut y = panel.bottom() - padding;
+        let (input, color) = match self.session {
+            Some(_) => (format!("> {}_", self.input), mcp::YELLOW),
+            None => ("Log in from the lobby to chat".to_string(), mcp::GRAY),
+        };
+        mcp::draw_text_ex(
+            &fit_line(&input, font, font_size, width, true),
+            panel.x + padding,
+            y,
+            params(color),
+        );
+        y -= line_height;
+
+        if let Some(err) = &self.error {
+            mcp::draw_text_ex(
+                &fit_line(err, font, font_size, width, false),
+                panel.x + padding,
+                y,
+                params(mcp::RED),
+            );
+            y -= line_height;
+        }
+
+        for line in self.messages.iter().rev() {
+            if y < panel.y + line_height {
+                break;
+            }
+            let text = format!("{}: {}", line.sender, line.message);
+            mcp::draw_text_ex(
+                &fit_line(&text, font, font_size, width, false),
+                panel.x + padding,
+                y,
+                params(mcp::WHITE),
+            );
+            y -= line_height;
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Deck builder: pick the cards of your deck from the whole collection.
+
+use macroquad::prelude as mcp;
+use opinion_rules::{CardCollection, DECK_SIZE, Deck, MAX_COPIES, MAX_DECK_NAME_LEN};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, DECKS_KEY};
+use crate::{CardLayout, Renderer, Shape, SizeRatio, fit_line};
+
+/// Collection cards shown side by side.
+const GRID_COLUMNS: usize = 5;
+
+/// What the player left the deck builder for.
+pub enum DeckBuilderExit {
+    Back,
+    /// Start a local match with the deck, it is legal.
+    Play,
+}
+
+/// Every deck the player built, and the one local matches are played with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeckList {
+    /// Never empty.
+    pub decks: Vec<Deck>,
+    pub selected: usize,
+}
+
+impl Default for DeckList {
+    fn default() -> Self {
+        Self {
+            decks: vec![Deck::new("My deck")],
+            selected: 0,
+        }
+    }
+}
+
+impl DeckList {
+    /// The stored decks, or a single empty one.
+    pub fn load() -> Self {
+        match storage::load::<DeckList>(DECKS_KEY) {
+            Some(mut list) if !list.decks.is_empty() => {
+                list.selected = list.selected.min(list.decks.len() - 1);
+                list
+            }
+            _ => DeckList::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        storage::save(DECKS_KEY, self);
+    }
+
+    pub fn current(&self) -> &Deck {
+        &self.decks[self.selected]
+    }
+
+    fn current_mut(&mut self) -> &mut Deck {
+        &mut self.decks[self.selected]
+    }
+
+    /// The selected deck, if it is legal with the cards of `col`.
+    pub fn playable(&self, col: &CardCollection) -> Option<Deck> {
+        let deck = self.current();
+        deck.check(col).is_ok().then(|| deck.clone())
+    }
+}
+
+pub struct DeckBuilder {
+    col: CardCollection,
+    decks: DeckList,
+    /// Collection rows scrolled past.
+    scroll: usize,
+    /// Why the last click did nothing, shown until the next one.
+    status: Option<String>,
+}
+
+impl DeckBuilder {
+    pub fn new(col: CardCollection, decks: DeckList) -> Self {
+        Self {
+            col,
+            decks,
+            scroll: 0,
+            status: None,
+        }
+    }
+
+    /// The decks as built so far, legal or not.
+    pub fn decks(&self) -> &DeckList {
+        &self.decks
+    }
+
+    fn grid_rect() -> mcp::Rect {
+        SizeRatio::new(0.03, 0.12, 0.62, 0.78)
+    }
+
+    fn list_rect() -> mcp::Rect {
+        SizeRatio::new(0.68, 0.12, 0.29, 0.7)
+    }
+
+    /// Size of one collection card, with the gap to the next one.
+    fn cell_size() -> (f32, f32) {
+        let w = DeckBuilder::grid_rect().w / GRID_COLUMNS as f32;
+        (w, w * 1.4)
+    }
+
+    fn visible_rows() -> usize {
+        let (_, h) = DeckBuilder::cell_size();
+        ((DeckBuilder::grid_rect().h / h) as usize).max(1)
+    }
+
+    /// Each collection card on screen with its index, scrolled rows left out.
+    fn card_rects(&self) -> Vec<(usize, mcp::Rect)> {
+        let grid = DeckBuilder::grid_rect();
+        let (w, h) = DeckBuilder::cell_size();
+        let first = self.scroll * GRID_COLUMNS;
+        let last = (first + DeckBuilder::visible_rows() * GRID_COLUMNS).min(self.col.col.len());
+
+        (first..last)
+            .map(|ind| {
+                let (row, col) = ((ind - first) / GRID_COLUMNS, (ind - first) % GRID_COLUMNS);
+                let rect = mcp::Rect::new(
+                    grid.x + col as f32 * w,
+                    grid.y + row as f32 * h,
+                    w * 0.92,
+                    h * 0.92,
+                );
+                (ind, rect)
+            })
+            .collect()
+    }
+
+    /// Collection index and copies of every card in the deck, in collection order.
+    fn deck_entries(&self) -> Vec<(usize, usize)> {
+        self.col
+            .col
+            .iter()
+            .enumerate()
+            .map(|(ind, card)| (ind, self.decks.current().count(&card.name)))
+            .filter(|&(_, copies)| copies > 0)
+            .collect()
+    }
+
+    fn line_height() -> f32 {
+        SizeRatio::get_y(0.04)
+    }
+
+    fn entry_rect(line: usize) -> mcp::Rect {
+        let list = DeckBuilder::list_rect();
+        let h = DeckBuilder::line_height();
+        mcp::Rect::new(list.x, list.y + h * (line as f32 + 1.5), list.w, h)
+    }
+
+    pub fn update(&mut self) -> Option<DeckBuilderExit> {
+        let before = self.decks.clone();
+        let exit = self.update_input();
+        // saved as it changes, the game may be closed from here
+        if self.decks != before {
+            self.decks.save();
+        }
+        exit
+    }
+
+    fn update_input(&mut self) -> Option<DeckBuilderExit> {
+        let ctrl = mcp::is_key_down(mcp::KeyCode::LeftControl)
+            || mcp::is_key_down(mcp::KeyCode::RightControl);
+        let shift =
+            mcp::is_key_down(mcp::KeyCode::LeftShift) || mcp::is_key_down(mcp::KeyCode::RightShift);
+
+        if mcp::is_key_pressed(mcp::KeyCode::Escape) {
+            return Some(DeckBuilderExit::Back);
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::Enter) {
+            match self.decks.current().check(&self.col) {
+                Ok(()) => return Some(DeckBuilderExit::Play),
+                Err(e) => self.status = Some(e.to_string()),
+            }
+        }
+
+        let count = self.decks.decks.len();
+        if mcp::is_key_pressed(mcp::KeyCode::Tab) {
+            self.decks.selected = if shift {
+                (self.decks.selected + count - 1) % count
+            } else {
+                (self.decks.selected + 1) % count
+            };
+            self.status = None;
+        }
+        if ctrl && mcp::is_key_pressed(mcp::KeyCode::N) {
+            self.decks
+                .decks
+                .push(Deck::new(&format!("Deck {}", count + 1)));
+            self.decks.selected = count;
+            self.status = None;
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::Delete) {
+            if !ctrl {
+                self.decks.current_mut().cards.clear();
+            } else if count > 1 {
+                self.decks.decks.remove(self.decks.selected);
+                self.decks.selected = self.decks.selected.min(count - 2);
+            }
+        }
+
+        while let Some(ch) = mcp::get_char_pressed() {
+            let name = &mut self.decks.current_mut().name;
+            if !ctrl
+                && (ch.is_alphanumeric() || " -_'".contains(ch))
+                && name.chars().count() < MAX_DECK_NAME_LEN
+            {
+                name.push(ch);
+            }
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::Backspace) {
+            self.decks.current_mut().name.pop();
+        }
+
+        let rows = self.col.col.len().div_ceil(GRID_COLUMNS);
+        let max_scroll = rows.saturating_sub(DeckBuilder::visible_rows());
+        let (_, wheel) = mcp::mouse_wheel();
+        if wheel < 0.0 {
+            self.scroll = (self.scroll + 1).min(max_scroll);
+        } else if wheel > 0.0 {
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+
+        let left = mcp::is_mouse_button_pressed(mcp::MouseButton::Left);
+        let right = mcp::is_mouse_button_pressed(mcp::MouseButton::Right);
+        if !(left || right) {
+            return None;
+        }
+        let mouse = mcp::mouse_position().into();
+
+        // left click adds a copy, right click takes one out
+        if let Some((ind, _)) = self
+            .card_rects()
+            .into_iter()
+            .find(|(_, r)| r.contains(mouse))
+        {
+            let name = self.col.col[ind].name.clone();
+            self.status = None;
+            if left && let Err(e) = self.decks.current_mut().add(&self.col, &name) {
+                self.status = Some(e.to_string());
+            }
+            if right {
+                self.decks.current_mut().remove(&name);
+            }
+        }
+
+        // any click on a deck line takes a copy out
+        let clicked = self
+            .deck_entries()
+            .into_iter()
+            .enumerate()
+            .map(|(line, entry)| (DeckBuilder::entry_rect(line), entry))
+            .take_while(|(rect, _)| rect.bottom() <= DeckBuilder::list_rect().bottom())
+            .find(|(rect, _)| rect.contains(mouse));
+        if let Some((_, (ind, _))) = clicked {
+            let name = self.col.col[ind].name.clone();
+            self.decks.current_mut().remove(&name);
+            self.status = None;
+        }
+
+        None
+    }
+
+    pub fn render(&self, font: &mcp::Font) {
+        let font_size = SizeRatio::get_y(0.04);
+        let params = |color| mcp::TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color,
+            ..Default::default()
+        };
+
+        let deck = self.decks.current();
+        let (x, y) = SizeRatio::get(0.03, 0.08);
+        let title = format!(
+            "Deck {}/{}: {}_   {}/{} cards",
+            self.decks.selected + 1,
+            self.decks.decks.len(),
+            deck.name,
+            deck.cards.len(),
+            DECK_SIZE
+        );
+        mcp::draw_text_ex(&title, x, y, params(mcp::WHITE));
+
+        let mouse = mcp::mouse_position().into();
+        let full = deck.cards.len() >= DECK_SIZE;
+        let mut hovered = None;
+        for (ind, rect) in self.card_rects() {
+            let card = &self.col.col[ind];
+            Renderer::render_card(card, rect, CardLayout::Minimal, font);
+
+            let copies = deck.count(&card.name);
+            if full || copies >= MAX_COPIES {
+                Shape::draw_rect(rect, mcp::Color::new(0.0, 0.0, 0.0, 0.5));
+            }
+            if copies > 0 {
+                let badge = format!("{}/{}", copies, MAX_COPIES);
+                mcp::draw_text_ex(
+                    &badge,
+                    rect.x,
+                    rect.bottom() - font_size * 0.2,
+                    params(mcp::YELLOW),
+                );
+            }
+            if rect.contains(mouse) {
+                hovered = Some(card);
+            }
+        }
+
+        let list = DeckBuilder::list_rect();
+        Shape::draw_rect(list, mcp::Color::new(0.0, 0.0, 0.0, 0.3));
+        mcp::draw_text_ex(
+            "In deck",
+            list.x,
+            list.y + DeckBuilder::line_height(),
+            params(mcp::LIGHTGRAY),
+        );
+        for (line, (ind, copies)) in self.deck_entries().into_iter().enumerate() {
+            let rect = DeckBuilder::entry_rect(line);
+            if rect.bottom() > list.bottom() {
+                break;
+            }
+            let card = &self.col.col[ind];
+            let text = format!("{}x {} ({})", copies, card.name, card.cost);
+            let color = if rect.contains(mouse) {
+                mcp::YELLOW
+            } else {
+                mcp::WHITE
+            };
+            let text = fit_line(&text, font, font_size, rect.w, false);
+            mcp::draw_text_ex(&text, rect.x, rect.bottom(), params(color));
+        }
+
+        let (x, y) = SizeRatio::get(0.68, 0.87);
+        let (msg, color) = match (&self.status, deck.check(&self.col)) {
+            (Some(status), _) => (status.clone(), mcp::RED),
+            (None, Ok(())) => ("Ready, Enter to play".to_string(), mcp::GREEN),
+            (None, Err(e)) => (e.to_string(), mcp::ORANGE),
+        };
+        let msg = fit_line(&msg, font, font_size * 0.8, list.w, false);
+        mcp::draw_text_ex(
+            &msg,
+            x,
+            y,
+            mcp::TextParams {
+                font_size: (font_size * 0.8) as u16,
+                ..params(color)
+            },
+        );
+
+        let help = "Click - Add   Right click - Remove   Wheel - Scroll   Type - Rename   \
+                    Del - Clear   Tab - Next deck   Ctrl+N - New deck   Ctrl+Del - Delete deck   \
+                    Enter - Play   Esc - Back";
+        let (x, y) = SizeRatio::get(0.03, 0.98);
+        mcp::draw_text_ex(
+            help,
+            x,
+            y,
+            mcp::TextParams {
+                font_size: (font_size * 0.6) as u16,
+                ..params(mcp::LIGHTGRAY)
+            },
+        );
+
+        if let Some(card) = hovered {
+            Renderer::render_inspector(card, mouse, font);
+        }
+    }
+}
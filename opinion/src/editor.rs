@@ -0,0 +1,390 @@
+//! Pixel editor for card art, saved back into the card definitions file.
+
+use macroquad::prelude as mcp;
+use opinion_rules::{CardCollection, CardImage, CardLoadError};
+
+use crate::{CardLayout, Renderer, SizeRatio, grid_cell_at};
+
+/// Undo steps kept per card.
+const UNDO_LIMIT: usize = 100;
+
+/// Most colours an imported PNG is quantised to, besides transparent.
+const IMPORT_COLORS: usize = 16;
+
+pub struct CardEditor {
+    col: CardCollection,
+    /// Card definitions file `col` was parsed from.
+    path: String,
+    /// Its contents, only the images are rewritten on save.
+    source: String,
+    selected: usize,
+    /// Images of the selected card before each stroke, most recent last.
+    undo: Vec<CardImage>,
+    redo: Vec<CardImage>,
+    /// Palette index new strokes paint with.
+    brush: u8,
+    /// Index painted while the mouse button is held. A stroke that starts
+    /// on a cell already in the brush colour erases to index 0 instead, so
+    /// a click toggles a pixel.
+    paint: Option<u8>,
+    /// Images changed since the last save.
+    dirty: bool,
+    /// Outcome of the last save, shown under the grid.
+    status: Option<(String, mcp::Color)>,
+}
+
+impl CardEditor {
+    pub async fn load(path: &str) -> Result<Self, CardLoadError> {
+        let source = mcp::load_string(path)
+            .await
+            .map_err(|e| CardLoadError::Io {
+                path: path.to_string(),
+                msg: e.to_string(),
+            })?;
+
+        Ok(Self {
+            col: CardCollection::parse(path, &source)?,
+            path: path.to_string(),
+            source,
+            selected: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            brush: 1,
+            paint: None,
+            dirty: false,
+            status: None,
+        })
+    }
+
+    /// The cards with their edited art, saved or not.
+    pub fn collection(&self) -> &CardCollection {
+        &self.col
+    }
+
+    fn image(&mut self) -> &mut CardImage {
+        &mut self.col.col[self.selected].img
+    }
+
+    fn grid_rect(&self) -> mcp::Rect {
+        let img = &self.col.col[self.selected].img;
+        Renderer::fit_image(img.rows, img.cols, SizeRatio::new(0.05, 0.12, 0.5, 0.68))
+    }
+
+    /// Square of each palette colour, in a row under the grid.
+    fn swatch_rects(&self) -> Vec<mcp::Rect> {
+        let size = SizeRatio::get_y(0.05);
+        let (x, y) = SizeRatio::get(0.05, 0.83);
+        (0..self.col.col[self.selected].img.palette.len())
+            .map(|i| mcp::Rect::new(x + i as f32 * size * 1.2, y, size, size))
+            .collect()
+    }
+
+    fn select(&mut self, ind: usize) {
+        self.selected = ind;
+        let colors = self.col.col[ind].img.palette.len();
+        self.brush = self.brush.min(colors.saturating_sub(1) as u8);
+        self.undo.clear();
+        self.redo.clear();
+        self.paint = None;
+    }
+
+    /// Remember the image before a change, dropping what could be redone.
+    fn checkpoint(&mut self) {
+        let before = self.image().clone();
+        self.undo.push(before);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) {
+        if let Some(img) = self.undo.pop() {
+            let current = std::mem::replace(self.image(), img);
+            self.redo.push(current);
+            self.dirty = true;
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(img) = self.redo.pop() {
+            let current = std::mem::replace(self.image(), img);
+            self.undo.push(current);
+            self.dirty = true;
+        }
+    }
+
+    /// PNG the art of the selected card is imported from and exported to:
+    /// `art/<card name>.png` next to the card definitions file.
+    fn art_path(&self) -> String {
+        let name: String = self.col.col[self.selected]
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let dir = self.path.rsplit_once('/').map_or(".", |(dir, _)| dir);
+        format!("{}/art/{}.png", dir, name)
+    }
+
+    /// Replace the selected card's art with its PNG, scaled to the card's grid.
+    async fn import_png(&mut self) {
+        let path = self.art_path();
+        let image = match mcp::load_image(&path).await {
+            Ok(image) => image,
+            Err(e) => {
+                self.status = Some((format!("{}: {}", path, e), mcp::RED));
+                return;
+            }
+        };
+
+        self.checkpoint();
+        let img = self.image();
+        *img = CardImage::from_rgba(
+            image.width as u32,
+            image.height as u32,
+            &image.bytes,
+            img.rows,
+            img.cols,
+            IMPORT_COLORS,
+            &img.palette,
+        );
+        self.dirty = true;
+        self.status = Some((format!("Imported {}", path), mcp::GREEN));
+    }
+
+    /// Write the selected card's art to its PNG, one pixel per cell.
+    fn export_png(&mut self) {
+        let path = self.art_path();
+        let img = &self.col.col[self.selected].img;
+        self.status = Some(
+            match write_png(&path, img.cols as u32, img.rows as u32, &img.to_rgba()) {
+                Ok(()) => (format!("Exported {}", path), mcp::GREEN),
+                Err(e) => (e, mcp::RED),
+            },
+        );
+    }
+
+    /// Returns `true` once the player wants to leave the editor.
+    pub async fn update(&mut self) -> bool {
+        let ctrl = mcp::is_key_down(mcp::KeyCode::LeftControl)
+            || mcp::is_key_down(mcp::KeyCode::RightControl);
+        let shift =
+            mcp::is_key_down(mcp::KeyCode::LeftShift) || mcp::is_key_down(mcp::KeyCode::RightShift);
+        let count = self.col.col.len();
+
+        if mcp::is_key_pressed(mcp::KeyCode::Escape) {
+            return true;
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::Left) {
+            self.select((self.selected + count - 1) % count);
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::Right) {
+            self.select((self.selected + 1) % count);
+        }
+
+        if ctrl && mcp::is_key_pressed(mcp::KeyCode::Z) {
+            if shift { self.redo() } else { self.undo() }
+        }
+        if ctrl && mcp::is_key_pressed(mcp::KeyCode::Y) {
+            self.redo();
+        }
+        if ctrl && mcp::is_key_pressed(mcp::KeyCode::S) {
+            self.save();
+        }
+        if !ctrl && mcp::is_key_pressed(mcp::KeyCode::I) {
+            self.import_png().await;
+        }
+        if !ctrl && mcp::is_key_pressed(mcp::KeyCode::E) {
+            self.export_png();
+        }
+        if !ctrl && mcp::is_key_pressed(mcp::KeyCode::C) {
+            self.checkpoint();
+            self.image().img.fill(0);
+            self.dirty = true;
+        }
+
+        // 1 to 9 pick the first nine colours, clicking a swatch picks any
+        let colors = self.col.col[self.selected].img.palette.len();
+        while let Some(ch) = mcp::get_char_pressed() {
+            if let Some(n) = ch.to_digit(10)
+                && (1..=colors as u32).contains(&n)
+            {
+                self.brush = n as u8 - 1;
+            }
+        }
+        if mcp::is_mouse_button_pressed(mcp::MouseButton::Left) {
+            let mouse = mcp::mouse_position().into();
+            if let Some(i) = self.swatch_rects().iter().position(|r| r.contains(mouse)) {
+                self.brush = i as u8;
+            }
+        }
+
+        self.update_paint();
+        false
+    }
+
+    fn update_paint(&mut self) {
+        if mcp::is_mouse_button_released(mcp::MouseButton::Left) {
+            // a stroke that changed nothing is not worth an undo step
+            if self.paint.take().is_some()
+                && self.undo.last().map(|img| &img.img)
+                    == Some(&self.col.col[self.selected].img.img)
+            {
+                self.undo.pop();
+            }
+            return;
+        }
+
+        let rect = self.grid_rect();
+        let img = &self.col.col[self.selected].img;
+        let Some((row, col)) = grid_cell_at(rect, img.rows, img.cols, mcp::mouse_position().into())
+        else {
+            return;
+        };
+        let current = img.get(row, col);
+
+        if mcp::is_mouse_button_pressed(mcp::MouseButton::Left) {
+            self.checkpoint();
+            self.paint = Some(if current == self.brush { 0 } else { self.brush });
+        }
+        if let Some(value) = self.paint
+            && current != value
+        {
+            self.image().set(row, col, value);
+            self.dirty = true;
+        }
+    }
+
+    /// Write the edited images into the card definitions file.
+    fn save(&mut self) {
+        let result = self
+            .col
+            .write_images(&self.path, &self.source)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                write_file(&self.path, &text)?;
+                Ok(text)
+            });
+
+        self.status = Some(match result {
+            Ok(text) => {
+                self.source = text;
+                self.dirty = false;
+                (format!("Saved to {}", self.path), mcp::GREEN)
+            }
+            Err(e) => (e, mcp::RED),
+        });
+    }
+
+    pub fn render(&self, font: &mcp::Font) {
+        let card = &self.col.col[self.selected];
+        let font_size = SizeRatio::get_y(0.04);
+        let params = |color| mcp::TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color,
+            ..Default::default()
+        };
+
+        let title = format!(
+            "< {} ({}/{}) >{}",
+            card.name,
+            self.selected + 1,
+            self.col.col.len(),
+            if self.dirty { " *" } else { "" }
+        );
+        let (x, y) = SizeRatio::get(0.05, 0.08);
+        mcp::draw_text_ex(&title, x, y, params(mcp::WHITE));
+
+        let rect = self.grid_rect();
+        let cell_size = rect.w / card.img.cols.max(1) as f32;
+        mcp::draw_rectangle(rect.x, rect.y, rect.w, rect.h, mcp::GRAY);
+        Renderer::render_card_img(&card.img, rect);
+        Renderer::render_grid(rect.x, rect.y, card.img.rows, card.img.cols, cell_size);
+
+        for (i, (swatch, color)) in self
+            .swatch_rects()
+            .iter()
+            .zip(&card.img.palette)
+            .enumerate()
+        {
+            mcp::draw_rectangle(swatch.x, swatch.y, swatch.w, swatch.h, mcp::GRAY);
+            mcp::draw_rectangle(
+                swatch.x,
+                swatch.y,
+                swatch.w,
+                swatch.h,
+                Renderer::color(color.color),
+            );
+            let (outline, thickness) = if i == self.brush as usize {
+                (mcp::YELLOW, 4.0)
+            } else {
+                (mcp::BLACK, 2.0)
+            };
+            mcp::draw_rectangle_lines(swatch.x, swatch.y, swatch.w, swatch.h, thickness, outline);
+        }
+
+        Renderer::render_card(
+            card,
+            SizeRatio::new(0.65, 0.12, 0.3, 0.6),
+            CardLayout::Full,
+            font,
+        );
+
+        let (x, y) = SizeRatio::get(0.05, 0.93);
+        if let Some((msg, color)) = &self.status {
+            mcp::draw_text_ex(msg, x, y, params(*color));
+        }
+        let help = "Left/Right - Card   1-9 - Colour   Click/drag - Paint   \
+                    Ctrl+Z/Ctrl+Y - Undo/Redo   C - Clear   I/E - Import/Export PNG   \
+                    Ctrl+S - Save   Esc - Back";
+        let (x, y) = SizeRatio::get(0.05, 0.98);
+        mcp::draw_text_ex(
+            help,
+            x,
+            y,
+            mcp::TextParams {
+                font_size: (font_size * 0.6) as u16,
+                ..params(mcp::LIGHTGRAY)
+            },
+        );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_file(path: &str, text: &str) -> Result<(), String> {
+    std::fs::write(path, text).map_err(|e| format!("{}: could not write file: {}", path, e))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_png(path: &str, width: u32, height: u32, rgba: &[u8]) -> Result<(), String> {
+    let err = |e: &dyn std::fmt::Display| format!("{}: could not write PNG: {}", path, e);
+
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir).map_err(|e| err(&e))?;
+    }
+    let file = std::fs::File::create(path).map_err(|e| err(&e))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|e| err(&e))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_png(_path: &str, _width: u32, _height: u32, _rgba: &[u8]) -> Result<(), String> {
+    Err("exporting card art is not available in the browser".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_file(_path: &str, _text: &str) -> Result<(), String> {
+    Err("saving card art is not available in the browser".to_string())
+}
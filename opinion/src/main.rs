@@ -1,12 +1,31 @@
 #![allow(dead_code)]
 
-use macroquad::prelude::coroutines::tweens::linear;
 //use macroquad::prelude::{camera::mouse};
-use macroquad::prelude::{self as mcp, debug};
+use macroquad::prelude as mcp;
+use opinion_rules::{
+    Card, CardCollection, CardImage, CardLoadError, CardType, Compensation, Deck, Effect,
+    GameAction, GameState, MAX_HAND_SIZE, MAX_LIFE, Mulligan, Opening, Phase, Player, Replay, Rgba,
+    SimpleRng, Tern,
+};
+use std::collections::VecDeque;
 //use std::{fmt, hint::select_unpredictable};
 // use rand::seq::SliceRandom;
 // use rand::{Rng, distr::Uniform, rng};
-use std::fmt; // for choose()
+
+mod anim;
+mod chat;
+mod deckbuilder;
+mod editor;
+mod net;
+mod piles;
+mod storage;
+use anim::{Animator, Ease, LAND_TIME, SLIDE_TIME, Tween};
+use chat::ChatPanel;
+use deckbuilder::{DeckBuilder, DeckBuilderExit, DeckList};
+use editor::CardEditor;
+use net::{OnlineMatch, Session};
+use piles::{PileBrowser, PileContents, Stack};
+use storage::{MATCH_KEY, SESSION_KEY, SavedMatch, Settings};
 
 //use macroquad::hash;
 //use macroquad::ui::root_ui;
@@ -17,142 +36,6 @@ use std::fmt; // for choose()
 // #[allow(unused_imports)]
 // use rdev::display_size;
 
-#[derive(Clone, Copy, Debug)]
-pub struct SimpleRng {
-    state: u64,
-}
-
-unsafe extern "C" {
-    fn get_seed() -> u64;
-}
-
-impl SimpleRng {
-    /// Create a new RNG from a user-provided seed.
-    pub fn from_seed(seed: u64) -> Self {
-        // avoid zero state (xorshift degenerate)
-        let state = if seed == 0 {
-            0x9e3779b97f4a7c15u64
-        } else {
-            seed
-        };
-        SimpleRng { state }
-    }
-
-    /// Create an RNG with a simple default seed.
-    /// On native, this will try to use the current time; on wasm it falls back to a fixed value.
-    pub fn from_entropy() -> Self {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            use std::time::{SystemTime, UNIX_EPOCH};
-            let nanos = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_nanos() as u64)
-                .unwrap_or(0);
-            // mix with address of a local var for tiny extra variability
-            let mix = (&nanos as *const u64 as u64).wrapping_mul(0xf39cc0605cedc835u64);
-            SimpleRng::from_seed(nanos ^ mix)
-        }
-
-        #[cfg(target_arch = "wasm32")]
-        {
-            // wasm: avoid pulling in web-sys/wasm-bindgen here.
-            // If you want real entropy in wasm, pass a seed from JS (e.g. crypto.getRandomValues).
-            // SimpleRng::from_seed(0x1234_5678_dead_beefu64)
-            let seed = unsafe { get_seed() };
-            SimpleRng::from_seed(seed)
-        }
-    }
-
-    /// xorshift64* implementation -> returns 64-bit random value
-    pub fn next_u64(&mut self) -> u64 {
-        // xorshift64* from Marsaglia / Sebastiano Vigna variant
-        let mut x = self.state;
-        // x must be non-zero; we enforced in from_seed
-        x ^= x >> 12;
-        x ^= x << 25;
-        x ^= x >> 27;
-        self.state = x;
-        x.wrapping_mul(2685821657736338717u64)
-    }
-
-    /// 32-bit random
-    pub fn next_u32(&mut self) -> u32 {
-        (self.next_u64() >> 32) as u32
-    }
-
-    /// generate a number in range [start, end) (usize)
-    pub fn gen_range(&mut self, start: usize, end: usize) -> usize {
-        let len = end.saturating_sub(start);
-        if len == 0 {
-            return start;
-        }
-        // simple modulo reduction (ok unless you need perfectly unbiased distribution)
-        (self.next_u64() as usize % len) + start
-    }
-
-    /// Fisher-Yates shuffle using this RNG
-    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
-        let n = slice.len();
-        if n <= 1 {
-            return;
-        }
-        for i in (1..n).rev() {
-            let j = self.gen_range(0, i + 1);
-            slice.swap(i, j);
-        }
-    }
-
-    /// Choose `k` distinct items from `items` (no replacement). Returns owned `Vec<T>` by cloning.
-    /// Requires T: Clone. Order of returned elements is random.
-    pub fn choose_multiple_without_replacement<T: Clone>(
-        &mut self,
-        items: &[T],
-        k: usize,
-    ) -> Vec<T> {
-        let k = k.min(items.len());
-        // If k is close to items.len(), it's cheaper to shuffle indices or copy then shuffle.
-        // We'll copy indices, shuffle, and take first k.
-        let mut idxs: Vec<usize> = (0..items.len()).collect();
-        self.shuffle(&mut idxs);
-        idxs.into_iter().take(k).map(|i| items[i].clone()).collect()
-    }
-
-    /// Choose `k` items with replacement (duplicates allowed)
-    pub fn choose_multiple_with_replacement<T: Clone>(&mut self, items: &[T], k: usize) -> Vec<T> {
-        if items.is_empty() || k == 0 {
-            return Vec::new();
-        }
-        (0..k)
-            .map(|_| {
-                let i = self.gen_range(0, items.len());
-                items[i].clone()
-            })
-            .collect()
-    }
-
-    /// Remove `n` random elements from the back-ish (i.e. pick n random indices and remove them).
-    /// This removes by swapping each chosen element to the end and popping — more efficient than repeated remove.
-    /// Returns removed elements in arbitrary order.
-    pub fn remove_random_n<T>(&mut self, vec: &mut Vec<T>, n: usize) -> Vec<T> {
-        let take = n.min(vec.len());
-        let mut removed = Vec::with_capacity(take);
-        // We will pick `take` distinct indices by performing a partial Fisher-Yates on indices.
-        // Equivalent to picking `take` unique random elements.
-        let mut i = vec.len();
-        for _ in 0..take {
-            i -= 1;
-            // pick j in [0..=i]
-            let j = self.gen_range(0, i + 1);
-            vec.swap(i, j);
-            // pop element at end (which was at j)
-            if let Some(x) = vec.pop() {
-                removed.push(x);
-            }
-        }
-        removed
-    }
-}
-
 static mut __UID: u32 = 0;
 pub fn new_uid() -> u32 {
     unsafe { __UID += 1 }
@@ -170,7 +53,7 @@ fn draw_fps() {
 }
 
 pub trait RectExt {
-    fn from_y(&mut self, y: f32) -> &mut mcp::Rect;
+    fn with_y(&mut self, y: f32) -> &mut mcp::Rect;
     fn with_height(&mut self, w: f32) -> &mut mcp::Rect;
     fn with_width(&mut self, h: f32) -> &mut mcp::Rect;
     fn clip_by(&mut self, d: f32) -> &mut mcp::Rect;
@@ -181,7 +64,7 @@ pub trait RectExt {
 }
 
 impl RectExt for mcp::Rect {
-    fn from_y(&mut self, y: f32) -> &mut mcp::Rect {
+    fn with_y(&mut self, y: f32) -> &mut mcp::Rect {
         self.y = y;
         self
     }
@@ -217,6 +100,7 @@ impl RectExt for mcp::Rect {
 }
 
 struct SizeRatio;
+#[allow(clippy::new_ret_no_self)]
 impl SizeRatio {
     pub fn new(x: f32, y: f32, w: f32, h: f32) -> mcp::Rect {
         mcp::Rect {
@@ -240,34 +124,6 @@ impl SizeRatio {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum CardType {
-    Attack,
-    Defense,
-    Magic,
-    Support,
-}
-
-#[derive(Clone)]
-pub struct CardImage {
-    rows: u16,
-    cols: u16,
-    img: Vec<bool>,
-    cell_size: u16,
-}
-impl CardImage {
-    pub fn new(rows: u16, cols: u16) -> Self {
-        let img = std::iter::repeat(false).take(20).collect();
-
-        Self {
-            rows,
-            cols,
-            img,
-            cell_size: 20,
-        }
-    }
-}
-
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ResizeEdge {
     Left,
@@ -350,77 +206,9 @@ fn resize_rect(rect: &mut mcp::Rect, edge: ResizeEdge, mouse_dx: f32, mouse_dy:
     }
 }
 
-#[derive(Clone)]
-pub struct Card {
-    id: u32,
-    img: CardImage,
-    pub name: String,
-    pub desc: String,
-    pub power: u32,
-    pub card_type: CardType,
-    rect: mcp::Rect,
-}
-
-impl Card {
-    pub fn new(
-        img: CardImage,
-        name: &str,
-        desc: &str,
-        power: u32,
-        card_type: CardType,
-        rect: mcp::Rect,
-    ) -> Self {
-        Self {
-            id: new_uid(),
-            img,
-            rect,
-            power,
-            card_type,
-            name: name.to_string(),
-            desc: desc.to_string(),
-        }
-    }
-
-    pub fn update(&mut self, mouse: &mut Mouse, ind: usize) {
-        let edge_margin = 10.0;
-
-        let (mx, my) = mcp::mouse_position();
-
-        if mcp::is_mouse_button_pressed(mcp::MouseButton::Left) {
-            let edge = mouse_near_edge(self.rect, mx, my, edge_margin);
-            if edge != ResizeEdge::None {
-                mouse.grab_it(Obj::Card(ind), Action::Resize(edge));
-                return;
-            }
-
-            if self.rect.contains(mcp::Vec2::new(mx, my)) {
-                mouse.grab_it(Obj::Card(ind), Action::Move);
-            }
-        }
-    }
-
-    fn resize(&mut self, (dx, dy): (f32, f32), edge: ResizeEdge) {
-        resize_rect(&mut self.rect, edge, dx, dy);
-    }
-
-    fn move_to(&mut self, (mx, my): (f32, f32)) {
-        self.rect.move_to(mcp::Vec2::new(mx, my));
-    }
-}
-
-impl Grabbable for Card {
+impl Grabbable for mcp::Rect {
     fn resize(&mut self, (dx, dy): (f32, f32), edge: &ResizeEdge) {
-        resize_rect(&mut self.rect, *edge, dx, dy);
-    }
-}
-
-impl fmt::Display for Card {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Card: {} Type: {:?} Power: {}\nDescription: {}\n",
-            self.name, self.card_type, self.power, self.desc
-        )
+        resize_rect(self, *edge, dx, dy);
     }
 }
 
@@ -534,134 +322,442 @@ fn draw_text_in_rect(text: &str, rect: mcp::Rect, font_size: u16, color: mcp::Co
     mcp::draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, mcp::GRAY);
 }
 
+/// Cut `text` down to fit `width`. Keeps the end when `keep_end` is set,
+/// so the input box always shows what is being typed.
+fn fit_line(text: &str, font: &mcp::Font, font_size: f32, width: f32, keep_end: bool) -> String {
+    let fits = |s: &str| mcp::measure_text(s, Some(font), font_size as u16, 1.0).width <= width;
+    if fits(text) {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        if keep_end {
+            chars.remove(0);
+        } else {
+            chars.pop();
+        }
+        let cut: String = chars.iter().collect();
+        let line = if keep_end {
+            format!("...{}", cut)
+        } else {
+            format!("{}...", cut)
+        };
+        if fits(&line) {
+            return line;
+        }
+    }
+    String::new()
+}
+
 struct Renderer;
 impl Renderer {
-    fn render_grid(x: f32, y: f32, rows: u16, cols: u16, cell_size: u16) {
-        for r in 0..rows {
-            let py = y + (r * cell_size) as f32;
-            mcp::draw_line(x, py, x + (cell_size * cols) as f32, py, 2.0, mcp::BLACK);
+    fn color(c: Rgba) -> mcp::Color {
+        mcp::Color::from_rgba(c.r, c.g, c.b, c.a)
+    }
+
+    /// Lines between the cells of a `rows * cols` grid with its top left corner at `x, y`.
+    fn render_grid(x: f32, y: f32, rows: u16, cols: u16, cell_size: f32) {
+        for r in 0..=rows {
+            let py = y + r as f32 * cell_size;
+            mcp::draw_line(x, py, x + cell_size * cols as f32, py, 2.0, mcp::BLACK);
         }
 
-        for c in 0..cols {
-            let px = x + (c * cell_size) as f32;
-            mcp::draw_line(px, y, px, y + (cell_size * rows) as f32, 2.0, mcp::BLACK);
+        for c in 0..=cols {
+            let px = x + c as f32 * cell_size;
+            mcp::draw_line(px, y, px, y + cell_size * rows as f32, 2.0, mcp::BLACK);
         }
     }
 
-    fn render_card_img(img: &CardImage) {
+    /// Largest area with square cells for a `rows * cols` image that fits
+    /// in `rect`, centred in it.
+    fn fit_image(rows: u16, cols: u16, rect: mcp::Rect) -> mcp::Rect {
+        let cell_size = (rect.w / cols.max(1) as f32).min(rect.h / rows.max(1) as f32);
+        let (w, h) = (cell_size * cols as f32, cell_size * rows as f32);
+        mcp::Rect::new(
+            rect.x + (rect.w - w) / 2.0,
+            rect.y + (rect.h - h) / 2.0,
+            w,
+            h,
+        )
+    }
+
+    /// Draw `img` in its palette colours, scaled to fit in `rect`.
+    /// Transparent pixels show what is behind.
+    fn render_card_img(img: &CardImage, rect: mcp::Rect) {
+        let area = Renderer::fit_image(img.rows, img.cols, rect);
+        let cell_size = area.w / img.cols.max(1) as f32;
+
         for r in 0..img.rows {
-            let y = (r * img.cell_size) as f32;
             for c in 0..img.cols {
-                let x = (c * img.cell_size) as f32;
-
-                mcp::draw_rectangle(x, y, img.cell_size as f32, img.cell_size as f32, mcp::BLUE);
+                let color = img.color(r, c);
+                if color.a > 0 {
+                    mcp::draw_rectangle(
+                        area.x + c as f32 * cell_size,
+                        area.y + r as f32 * cell_size,
+                        cell_size,
+                        cell_size,
+                        Renderer::color(color),
+                    );
+                }
             }
         }
-
-        Renderer::render_grid(0.0, 0.0, img.rows, img.cols, img.cell_size);
     }
 
-    fn render_card_minimal(card: &Card, font: &mcp::Font) {
-        let offset = 4.0;
-        let border = 4.0;
-        let font_size = card.rect.h / 6.0;
-        let dec_font_size = font_size / 1.5;
+    /// Draw `card` in `rect` with as much detail as `layout` asks for and
+    /// the size allows.
+    fn render_card(card: &Card, rect: mcp::Rect, layout: CardLayout, font: &mcp::Font) {
+        let layout = layout.min(CardLayout::for_size(rect));
+        let border = (rect.w.min(rect.h) * 0.04).max(1.0);
+        let mut inner = rect;
+        inner.clip_by(border);
 
-        let mut img = card.rect.clone();
-        img.with_height(card.rect.h / 2.0).clip_by(border);
+        Shape::draw_rect(rect, mcp::DARKGRAY);
 
-        Shape::draw_rect(card.rect, mcp::DARKGRAY);
-        // Shape::draw_rect(img, BLUE);
+        match layout {
+            CardLayout::Tiny => {
+                Renderer::render_card_img(&card.img, inner);
+            }
+            CardLayout::Minimal | CardLayout::Full => {
+                // the art sits under the corner badges, the name right below it
+                let name_h = rect.h * 0.12;
+                let art_h = match layout {
+                    CardLayout::Full => rect.h * 0.42,
+                    _ => inner.h - name_h,
+                };
+                let art = mcp::Rect::new(inner.x, inner.y, inner.w, art_h);
+                let name = mcp::Rect::new(inner.x, art.bottom(), inner.w, name_h);
+
+                Shape::draw_rect(art, mcp::Color::new(0.0, 0.0, 0.0, 0.3));
+                Renderer::render_card_img(&card.img, art);
+                Renderer::render_line(&card.name, name, mcp::WHITE, font);
+
+                if layout == CardLayout::Full {
+                    let type_line = mcp::Rect::new(inner.x, name.bottom(), inner.w, rect.h * 0.09);
+                    // leave the bottom right corner to the power badge
+                    let badge = rect.w.min(rect.h) / 4.0;
+                    let text = mcp::Rect::new(
+                        inner.x,
+                        type_line.bottom(),
+                        inner.w,
+                        inner.bottom() - type_line.bottom() - badge,
+                    );
+                    let label = mcp::Rect::new(
+                        type_line.x + type_line.h,
+                        type_line.y,
+                        type_line.w - type_line.h * 2.0,
+                        type_line.h,
+                    );
+
+                    Renderer::render_type_icon(
+                        &card.card_type,
+                        mcp::Rect::new(type_line.x, type_line.y, type_line.h, type_line.h),
+                    );
+                    Renderer::render_line(
+                        &format!("{:?}", card.card_type),
+                        label,
+                        mcp::LIGHTGRAY,
+                        font,
+                    );
+                    Renderer::render_text_box(
+                        &[(&card.desc, mcp::WHITE)],
+                        text,
+                        (text.h / 6.0).min(text.w / 8.0),
+                        font,
+                    );
+                }
+            }
+        }
 
-        // for c in card.name.chars() {
-        //     let dim = mcp::measure_text(&c.to_string(), Some(font), font_size as u16, 1.0);
-        //     println!("dim: {} {:?}", String::from(c), dim);
-        // }
+        // corners: cost top left, type top right, power bottom right
+        Renderer::render_cost_badge(card, rect, font);
+        if layout != CardLayout::Full {
+            let size = rect.w.min(rect.h) / 5.0;
+            Renderer::render_type_icon(
+                &card.card_type,
+                mcp::Rect::new(rect.right() - size - border, rect.y + border, size, size),
+            );
+        }
+        if card.card_type.is_permanent() {
+            Renderer::render_power_badge(card, rect, font);
+        }
+    }
 
-        let mut y = img.h + img.y + font_size;
+    /// Smallest font size worth drawing, anything below is skipped.
+    const MIN_FONT_SIZE: f32 = 6.0;
+
+    /// One line of text filling the height of `rect`, shrunk or cut short
+    /// to fit its width.
+    fn render_line(text: &str, rect: mcp::Rect, color: mcp::Color, font: &mcp::Font) {
+        let mut font_size = rect.h * 0.8;
+        let width = |size: f32| mcp::measure_text(text, Some(font), size as u16, 1.0).width;
+        // shrink down to two thirds, then cut the text instead
+        if width(font_size) > rect.w {
+            font_size = (font_size * rect.w / width(font_size)).max(rect.h * 0.55);
+        }
+        if font_size < Renderer::MIN_FONT_SIZE {
+            return;
+        }
 
+        let line = fit_line(text, font, font_size, rect.w, false);
         mcp::draw_text_ex(
-            &card.name,
-            img.x,
-            y,
+            &line,
+            rect.x,
+            rect.y + (rect.h + font_size * 0.7) / 2.0,
             mcp::TextParams {
-                font: Some(&font),
+                font: Some(font),
                 font_size: font_size as u16,
-                color: mcp::WHITE,
+                color,
                 ..Default::default()
             },
         );
+    }
 
-        y += dec_font_size + offset;
-
-        draw_text_in_rect_char_wrap(
-            &card.desc,
-            mcp::Rect::new(img.x, y, img.w, img.h),
-            font,
-            dec_font_size as u16,
-            mcp::WHITE,
-        );
+    /// Word-wrapped paragraphs, each in its own colour, with the largest
+    /// font up to `max_font_size` that fits all of them in `rect`.
+    fn render_text_box(
+        paragraphs: &[(&str, mcp::Color)],
+        rect: mcp::Rect,
+        max_font_size: f32,
+        font: &mcp::Font,
+    ) {
+        let mut font_size = max_font_size;
+        let lines = loop {
+            let lines: Vec<(String, mcp::Color)> = paragraphs
+                .iter()
+                .flat_map(|&(text, color)| {
+                    wrap_words(text, font, font_size, rect.w)
+                        .into_iter()
+                        .map(move |line| (line, color))
+                })
+                .collect();
+            if lines.len() as f32 * font_size * 1.2 <= rect.h
+                || font_size <= Renderer::MIN_FONT_SIZE
+            {
+                break lines;
+            }
+            font_size *= 0.9;
+        };
+        if font_size < Renderer::MIN_FONT_SIZE {
+            return;
+        }
 
-        // Renderer::render_card_img(&card.img);
+        for (ind, (line, color)) in lines.iter().enumerate() {
+            let y = rect.y + font_size * (1.0 + ind as f32 * 1.2);
+            if y > rect.bottom() {
+                break;
+            }
+            mcp::draw_text_ex(
+                line,
+                rect.x,
+                y,
+                mcp::TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: *color,
+                    ..Default::default()
+                },
+            );
+        }
     }
 
-    fn render_card(card: &Card, font: &mcp::Font) {
-        let offset = 4.0;
-        let border = 4.0;
-        let font_size = 30.0;
-        let dec_font_size = 20.0;
+    /// Enlarged `card` next to `anchor`, with everything about it spelled
+    /// out: type rules, cost, power and what each effect keyword means.
+    fn render_inspector(card: &Card, anchor: mcp::Vec2, font: &mcp::Font) {
+        let (screen_w, screen_h) = (mcp::screen_width(), mcp::screen_height());
+        let card_h = screen_h * 0.55;
+        let card_w = card_h / 1.4;
+        let panel_w = (card_w * 1.2).min(screen_w * 0.4);
+        let gap = screen_h * 0.02;
+        let total_w = card_w + gap + panel_w;
+
+        // beside the pointer on whichever side has room, kept on screen
+        let x = if anchor.x + gap + total_w <= screen_w {
+            anchor.x + gap
+        } else {
+            anchor.x - gap - total_w
+        }
+        .clamp(0.0, (screen_w - total_w).max(0.0));
+        let y = (anchor.y - card_h / 2.0).clamp(0.0, (screen_h - card_h).max(0.0));
+
+        let card_rect = mcp::Rect::new(x, y, card_w, card_h);
+        let panel = mcp::Rect::new(card_rect.right() + gap, y, panel_w, card_h);
+        Renderer::render_card(card, card_rect, CardLayout::Full, font);
+        Shape::draw_rect(panel, mcp::Color::new(0.0, 0.0, 0.0, 0.85));
+
+        let mut stats = format!("{:?}  Cost {}", card.card_type, card.cost);
+        if card.card_type.is_permanent() {
+            stats.push_str(&format!("  Power {}", card.power));
+            if card.damage > 0 {
+                stats.push_str(&format!(" ({} damage taken)", card.damage));
+            }
+        }
+
+        let mut keywords: Vec<String> = Vec::new();
+        for effect in &card.effects {
+            let text = effect.explain();
+            if !keywords.contains(&text) {
+                keywords.push(text);
+            }
+        }
+
+        let mut paragraphs = vec![
+            (card.name.as_str(), mcp::WHITE),
+            (stats.as_str(), mcp::LIGHTGRAY),
+            (card.card_type.explain(), mcp::LIGHTGRAY),
+        ];
+        if !card.desc.is_empty() {
+            paragraphs.push((card.desc.as_str(), mcp::WHITE));
+        }
+        paragraphs.extend(keywords.iter().map(|k| (k.as_str(), mcp::SKYBLUE)));
 
-        let mut img = card.rect.clone();
-        img.with_height(card.rect.h / 2.0).clip_by(border);
+        let mut text = panel;
+        text.clip_by(gap);
+        Renderer::render_text_box(&paragraphs, text, screen_h * 0.035, font);
+    }
 
-        Shape::draw_rect(card.rect, mcp::DARKGRAY);
-        // Shape::draw_rect(img, BLUE);
+    /// Shape standing for the card type, filling `rect`: a triangle for
+    /// Attack, a square for Defense, a hexagon for Magic and a circle for Support.
+    fn render_type_icon(card_type: &CardType, rect: mcp::Rect) {
+        let (cx, cy) = (rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+        let radius = rect.w.min(rect.h) / 2.0;
+        let (sides, rotation, color) = match card_type {
+            CardType::Attack => (3, -90.0, mcp::MAROON),
+            CardType::Defense => (4, 45.0, mcp::DARKGREEN),
+            CardType::Magic => (6, 0.0, mcp::PURPLE),
+            CardType::Support => (24, 0.0, mcp::GOLD),
+        };
 
-        // for c in card.name.chars() {
-        //     let dim = mcp::measure_text(&c.to_string(), Some(font), font_size as u16, 1.0);
-        //     println!("dim: {} {:?}", String::from(c), dim);
-        // }
+        mcp::draw_poly(cx, cy, sides, radius, rotation, mcp::BLACK);
+        mcp::draw_poly(cx, cy, sides, radius * 0.75, rotation, color);
+    }
 
-        let mut y = img.h + img.y + font_size;
+    /// Remaining power in a circle on the bottom right corner of the card.
+    fn render_power_badge(card: &Card, rect: mcp::Rect, font: &mcp::Font) {
+        let radius = rect.w.min(rect.h) / 8.0;
+        let (cx, cy) = (rect.right() - radius, rect.bottom() - radius);
+        let text = card.power.saturating_sub(card.damage).to_string();
+        let font_size = (radius * 1.5) as u16;
+        let dims = mcp::measure_text(&text, Some(font), font_size, 1.0);
+        let color = match card.card_type {
+            CardType::Defense => mcp::DARKGREEN,
+            _ => mcp::MAROON,
+        };
 
+        mcp::draw_circle(cx, cy, radius, color);
         mcp::draw_text_ex(
-            &card.name,
-            img.x,
-            y,
+            &text,
+            cx - dims.width / 2.0,
+            cy + dims.offset_y / 2.0,
             mcp::TextParams {
-                font: Some(&font),
-                font_size: font_size as u16,
+                font: Some(font),
+                font_size,
                 color: mcp::WHITE,
                 ..Default::default()
             },
         );
+    }
 
-        y += dec_font_size + offset;
+    /// Mana cost in a circle on the top left corner of the card.
+    fn render_cost_badge(card: &Card, rect: mcp::Rect, font: &mcp::Font) {
+        let radius = rect.w.min(rect.h) / 8.0;
+        let (cx, cy) = (rect.x + radius, rect.y + radius);
+        let text = card.cost.to_string();
+        let font_size = (radius * 1.5) as u16;
+        let dims = mcp::measure_text(&text, Some(font), font_size, 1.0);
 
-        draw_text_in_rect_char_wrap(
-            &card.desc,
-            mcp::Rect::new(img.x, y, img.w, img.h),
-            font,
-            dec_font_size as u16,
-            mcp::WHITE,
+        mcp::draw_circle(cx, cy, radius, mcp::DARKBLUE);
+        mcp::draw_text_ex(
+            &text,
+            cx - dims.width / 2.0,
+            cy + dims.offset_y / 2.0,
+            mcp::TextParams {
+                font: Some(font),
+                font_size,
+                color: mcp::WHITE,
+                ..Default::default()
+            },
         );
+    }
+
+    fn render_card_back(rect: mcp::Rect) {
+        let border = 4.0;
+        let mut inner = rect;
+        inner.clip_by(border);
 
-        Renderer::render_card_img(&card.img);
+        Shape::draw_rect(rect, mcp::DARKGRAY);
+        Shape::draw_rect(inner, mcp::DARKBLUE);
     }
 }
 
-fn handle_grid_click(grid: &mut Vec<bool>, cols: usize, rows: usize, cell_size: f32) {
-    if mcp::is_mouse_button_pressed(mcp::MouseButton::Left) {
-        let (mx, my) = mcp::mouse_position();
+/// How much of a card is drawn, from most to least detailed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CardLayout {
+    /// Art only with the badges, for cards in the arena or anything very small.
+    Tiny,
+    /// Name and art, for cards in hand.
+    Minimal,
+    /// Name, art, type and effect text, for cards looked at up close.
+    Full,
+}
+
+impl CardLayout {
+    /// The most detailed layout that stays readable at the size of `rect`.
+    pub fn for_size(rect: mcp::Rect) -> Self {
+        match rect.w.min(rect.h / 1.4) {
+            w if w >= 140.0 => CardLayout::Full,
+            w if w >= 50.0 => CardLayout::Minimal,
+            _ => CardLayout::Tiny,
+        }
+    }
+}
+
+/// Split `text` into lines no wider than `width`. Words too long for a
+/// line on their own are broken between characters.
+fn wrap_words(text: &str, font: &mcp::Font, font_size: f32, width: f32) -> Vec<String> {
+    let fits = |s: &str| mcp::measure_text(s, Some(font), font_size as u16, 1.0).width <= width;
+    let mut lines = Vec::new();
+    let mut line = String::new();
 
-        let col = (mx / cell_size) as usize;
-        let row = (my / cell_size) as usize;
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if fits(&joined) {
+            line = joined;
+            continue;
+        }
 
-        if col < cols && row < rows {
-            let idx = row * cols + col;
-            grid[idx] = !grid[idx]; // toggle value
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
         }
+        for ch in word.chars() {
+            line.push(ch);
+            if !fits(&line) && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, ch.to_string()));
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
     }
+    lines
+}
+
+/// Row and column of the cell of a `rows * cols` grid drawn in `rect` that
+/// holds `pos`, if any.
+fn grid_cell_at(rect: mcp::Rect, rows: u16, cols: u16, pos: mcp::Vec2) -> Option<(u16, u16)> {
+    if !rect.contains(pos) {
+        return None;
+    }
+
+    let col = ((pos.x - rect.x) / rect.w * cols as f32) as u16;
+    let row = ((pos.y - rect.y) / rect.h * rows as f32) as u16;
+    (row < rows && col < cols).then_some((row, col))
 }
 
 fn window_conf() -> mcp::Conf {
@@ -674,54 +770,85 @@ fn window_conf() -> mcp::Conf {
         window_title: "Opinion".to_owned(),
         // fullscreen: true,
         window_resizable: true,
-        window_width: width as i32,
-        window_height: height as i32,
+        window_width: width,
+        window_height: height,
         ..Default::default()
     }
 }
 
-enum Tern {
-    Player1,
-    Player2,
+/// A row of cards in a `PlayerView`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pile {
+    Hand,
+    Arena,
 }
 
-pub struct Player {
-    hand: Vec<Card>,
-    library: Vec<Card>,
-    graveyard: Vec<Card>,
+/// Where one player's cards are on screen, one rect per hand and arena slot.
+pub struct PlayerView {
+    /// The player's piles as far as the animations got, the rules `Player`
+    /// is ahead of it while an `Animator` is busy.
+    shown: Player,
+    hand_rects: Vec<mcp::Rect>,
+    arena_rects: Vec<mcp::Rect>,
+    /// Cards easing into a new slot after their row was laid out again.
+    slides: Vec<(Pile, usize, Tween)>,
+    /// Piles settling back after a card landed on them.
+    bumps: Vec<(Stack, Tween)>,
 
     arena_rect: mcp::Rect,
     hand_rect: mcp::Rect,
+    /// Top card of the graveyard is shown here, beside the arena.
+    graveyard_rect: mcp::Rect,
+    /// The library is shown face down here, beside the hand.
+    library_rect: mcp::Rect,
     def_card_size: (f32, f32),
 }
 
-impl Player {
-    pub fn new(col: &CardCollection) -> Self {
-        let mut library = col.get_random(20);
-        for c in &library {
-            debug!("{}", c);
-        }
-
-        let hand = library.drain(library.len().saturating_sub(3)..).collect();
-
-        println!("---------------------------");
-
-        for c in &hand {
-            debug!("{}", c);
-        }
+impl PlayerView {
+    /// The local player sits at the bottom of the screen, the other one
+    /// mirrored at the top. The opening hand starts in the library, it is
+    /// dealt by the `GameEvent`s of the new match.
+    pub fn new(player: &Player, at_bottom: bool) -> Self {
+        let (arena_rect, hand_rect) = if at_bottom {
+            (
+                SizeRatio::new(0.2, 0.52, 0.6, 0.18),
+                SizeRatio::new(0.1, 0.78, 0.8, 0.18),
+            )
+        } else {
+            (
+                SizeRatio::new(0.2, 0.28, 0.6, 0.18),
+                SizeRatio::new(0.1, 0.04, 0.8, 0.18),
+            )
+        };
+        let def_card_size = (hand_rect.w / MAX_HAND_SIZE as f32, hand_rect.h);
+        let graveyard_rect = mcp::Rect::new(
+            arena_rect.right() + SizeRatio::get_x(0.03),
+            arena_rect.y,
+            def_card_size.0.min(arena_rect.h / 1.4),
+            arena_rect.h,
+        );
+        let library_rect = mcp::Rect::new(
+            hand_rect.right() + SizeRatio::get_x(0.01),
+            hand_rect.y,
+            SizeRatio::get_x(0.08),
+            hand_rect.h,
+        );
 
-        let arena_rect = SizeRatio::new(0.2, 0.52, 0.6, 0.18);
-        let hand_rect = SizeRatio::new(0.1, 0.78, 0.8, 0.18);
-        let def_card_size = (hand_rect.w / 7.0, hand_rect.h);
+        let mut shown = player.clone();
+        shown.library.append(&mut shown.hand);
 
         let mut ret = Self {
-            hand,
-            library,
-            graveyard: Vec::new(),
+            shown,
+            hand_rects: Vec::new(),
+            arena_rects: Vec::new(),
+            slides: Vec::new(),
+            bumps: Vec::new(),
             def_card_size,
 
             arena_rect,
             hand_rect,
+            graveyard_rect,
+            library_rect,
         };
 
         ret.update_card_position();
@@ -729,119 +856,1310 @@ impl Player {
         ret
     }
 
-    pub fn update_card_position(&mut self) {
-        let x = self.hand_rect.x;
-        let offset = 2.0;
+    /// Grab a hand card for dragging when it is clicked.
+    pub fn update_hand(&mut self, mouse: &mut Mouse) {
+        if !mcp::is_mouse_button_pressed(mcp::MouseButton::Left) {
+            return;
+        }
 
-        for (ind, card) in self.hand.iter_mut().enumerate() {
-            let px = x + ind as f32 * (self.def_card_size.0 + offset);
-            card.rect.update_pos_n_size(
-                px,
-                self.hand_rect.y,
-                self.def_card_size.0,
-                self.def_card_size.1,
-            );
+        let (mx, my) = mcp::mouse_position();
+        if let Some(ind) = self
+            .hand_rects
+            .iter()
+            .position(|r| r.contains(mcp::Vec2::new(mx, my)))
+        {
+            mouse.grab_it(Obj::Card(ind), Action::Move);
         }
     }
 
-    fn render_arena(&self, font: &mcp::Font) {
-        self.arena_rect.render();
+    /// Raise the hand card at `ind` towards the arena, or put it back in line.
+    pub fn lift_hand_card(&mut self, ind: usize, lifted: bool) {
+        let slot = self.slot_rect(Pile::Hand, ind, self.hand_rects.len());
+        let towards_arena = if self.arena_rect.y < self.hand_rect.y {
+            -1.0
+        } else {
+            1.0
+        };
+        if let Some(rect) = self.hand_rects.get_mut(ind) {
+            rect.y = slot.y;
+            if lifted {
+                rect.y += towards_arena * rect.h * 0.25;
+            }
+        }
+    }
 
-        let arena_text = "Arena";
-        let arena_text_width =
-            mcp::measure_text(arena_text, Some(font), self.arena_rect.h as u16, 1.);
+    /// Whether the dragged hand card at `ind` was let go over the arena.
+    pub fn dropped_in_arena(&self, ind: usize) -> bool {
+        self.hand_rects
+            .get(ind)
+            .is_some_and(|rect| self.arena_rect.contains(rect.center()))
+    }
 
-        mcp::draw_text_ex(
-            arena_text,
-            self.arena_rect.center().x - arena_text_width.width / 2.0,
-            self.arena_rect.bottom() - 20.0,
-            mcp::TextParams {
-                font: Some(&font),
-                font_size: self.arena_rect.h as u16,
-                color: mcp::WHITE,
-                ..Default::default()
-            },
-        );
+    /// The shown card under `pos`, topmost first. Hand cards only count
+    /// when `show_hand` is set, a hidden hand gives nothing away.
+    pub fn card_at<'a>(&'a self, pos: mcp::Vec2, show_hand: bool) -> Option<&'a Card> {
+        let player = &self.shown;
+        let hit = |cards: &'a [Card], rects: &[mcp::Rect]| {
+            cards
+                .iter()
+                .zip(rects)
+                .rev()
+                .find(|(_, rect)| rect.contains(pos))
+                .map(|(card, _)| card)
+        };
+
+        let hand = if show_hand {
+            hit(&player.hand, &self.hand_rects)
+        } else {
+            None
+        };
+        hand.or_else(|| hit(&player.arena, &self.arena_rects))
+            .or_else(|| {
+                player
+                    .graveyard
+                    .last()
+                    .filter(|_| self.graveyard_rect.contains(pos))
+            })
     }
 
-    pub fn render_hand(&self, font: &mcp::Font) {
-        self.hand_rect.render();
-        self.render_arena(font);
+    /// Move every shown card towards its slot, sliding over from where it is.
+    pub fn update_card_position(&mut self) {
+        let (old_hand, old_arena) = (self.hand_rects.clone(), self.arena_rects.clone());
+        PlayerView::layout_row(
+            &mut self.hand_rects,
+            self.shown.hand.len(),
+            self.hand_rect,
+            self.def_card_size,
+        );
+        PlayerView::layout_row(
+            &mut self.arena_rects,
+            self.shown.arena.len(),
+            self.arena_rect,
+            self.def_card_size,
+        );
 
-        for card in self.hand.iter() {
-            Renderer::render_card_minimal(&card, font);
+        self.slides.clear();
+        for (pile, old) in [(Pile::Hand, old_hand), (Pile::Arena, old_arena)] {
+            let rects = match pile {
+                Pile::Hand => &mut self.hand_rects,
+                Pile::Arena => &mut self.arena_rects,
+            };
+            for (ind, (rect, from)) in rects.iter_mut().zip(old).enumerate() {
+                if *rect != from {
+                    let tween = Tween::new(from, *rect, SLIDE_TIME, Ease::OutQuad);
+                    self.slides.push((pile, ind, tween));
+                    *rect = from;
+                }
+            }
         }
     }
-}
 
-pub struct Game {
-    player1: Player,
-    player2: Player,
-    turn: Tern,
+    /// Advance the cards sliding into their slots, and the piles settling,
+    /// by `dt` seconds.
+    pub fn update_slides(&mut self, dt: f32) {
+        for (_, tween) in &mut self.bumps {
+            tween.advance(dt);
+        }
+        self.bumps.retain(|(_, tween)| !tween.is_done());
+
+        for (pile, ind, tween) in &mut self.slides {
+            tween.advance(dt);
+            let rects = match pile {
+                Pile::Hand => &mut self.hand_rects,
+                Pile::Arena => &mut self.arena_rects,
+            };
+            if let Some(rect) = rects.get_mut(*ind) {
+                *rect = tween.rect();
+            }
+        }
+        self.slides.retain(|(_, _, tween)| !tween.is_done());
+    }
 
-    is_running: bool,
-    card_collection: Vec<Card>,
-}
+    pub fn is_sliding(&self) -> bool {
+        !self.slides.is_empty()
+    }
 
-pub struct CardCollection {
-    col: Vec<Card>,
-}
+    /// The cards of `stack`, top card last.
+    pub fn pile(&self, stack: Stack) -> &[Card] {
+        match stack {
+            Stack::Library => &self.shown.library,
+            Stack::Graveyard => &self.shown.graveyard,
+        }
+    }
 
-impl CardCollection {
-    pub fn generate() -> Self {
-        Self {
-            col: generate_card_collection(),
+    /// Where `stack` is drawn when it is not moving.
+    fn resting_rect(&self, stack: Stack) -> mcp::Rect {
+        match stack {
+            Stack::Library => self.library_rect,
+            Stack::Graveyard => self.graveyard_rect,
         }
     }
 
-    pub fn get_random(&self, count: usize) -> Vec<Card> {
-        let mut rng = SimpleRng::from_entropy();
-        std::iter::repeat_with(|| self.col[rng.gen_range(0, self.col.len() - 1)].clone())
-            .take(count)
-            .collect()
+    /// Where `stack` is drawn right now.
+    pub fn stack_rect(&self, stack: Stack) -> mcp::Rect {
+        self.bumps
+            .iter()
+            .find(|(s, _)| *s == stack)
+            .map_or(self.resting_rect(stack), |(_, tween)| tween.rect())
     }
-}
 
-fn generate_card_collection() -> Vec<Card> {
-    let mut col = vec![];
+    /// Make `stack` swell and settle, after a card landed on it.
+    pub fn bump(&mut self, stack: Stack) {
+        const GROWTH: f32 = 0.15;
+        let rect = self.resting_rect(stack);
+        let (dw, dh) = (rect.w * GROWTH, rect.h * GROWTH);
+        let grown = mcp::Rect::new(
+            rect.x - dw / 2.0,
+            rect.y - dh / 2.0,
+            rect.w + dw,
+            rect.h + dh,
+        );
 
-    let card_width = SizeRatio::get_x(0.4);
-    let card_height = SizeRatio::get_y(0.6);
+        self.bumps.retain(|(s, _)| *s != stack);
+        self.bumps
+            .push((stack, Tween::new(grown, rect, LAND_TIME, Ease::OutBack)));
+    }
 
-    let def_rect = mcp::Rect::new(0., 0., card_width, card_height);
+    /// Where slot `ind` of `pile` is when it holds `count` cards.
+    fn slot_rect(&self, pile: Pile, ind: usize, count: usize) -> mcp::Rect {
+        let row = match pile {
+            Pile::Hand => self.hand_rect,
+            Pile::Arena => self.arena_rect,
+        };
+        PlayerView::row_slot(ind, count, row, self.def_card_size)
+    }
 
-    let (img_rows, img_cols) = (10, 10);
-    let img = CardImage::new(img_rows, img_cols);
+    /// Take the shown card at `ind` out of `pile`, the rest close the gap.
+    /// Returns where the card was.
+    fn take(&mut self, pile: Pile, ind: usize) -> Option<mcp::Rect> {
+        let (cards, rects) = match pile {
+            Pile::Hand => (&mut self.shown.hand, &mut self.hand_rects),
+            Pile::Arena => (&mut self.shown.arena, &mut self.arena_rects),
+        };
+        if ind >= cards.len() || ind >= rects.len() {
+            return None;
+        }
 
-    col.push(Card::new(
-        img.clone(),
-        "Smithy",
-        "Card draw 1",
-        0,
-        CardType::Support,
-        def_rect,
-    ));
+        cards.remove(ind);
+        let rect = rects.remove(ind);
+        self.update_card_position();
+        Some(rect)
+    }
 
-    col.push(Card::new(
-        img.clone(),
-        "Smithy2",
-        "Card draw 2",
-        0,
-        CardType::Support,
-        def_rect,
-    ));
+    /// Lay `count` cards out left to right inside `rect`, overlapping them when
+    /// there are more than fit side by side.
+    fn layout_row(rects: &mut Vec<mcp::Rect>, count: usize, rect: mcp::Rect, size: (f32, f32)) {
+        rects.resize(count, mcp::Rect::default());
+        for (ind, card) in rects.iter_mut().enumerate() {
+            *card = PlayerView::row_slot(ind, count, rect, size);
+        }
+    }
 
-    col.push(Card::new(
-        img.clone(),
-        "Smithy3",
-        "Card draw 3",
-        0,
-        CardType::Support,
-        def_rect,
-    ));
+    fn row_slot(ind: usize, count: usize, rect: mcp::Rect, (w, h): (f32, f32)) -> mcp::Rect {
+        let offset = 2.0;
+        let mut step = w + offset;
+        if count > 1 {
+            step = step.min((rect.w - w) / (count - 1) as f32);
+        }
+        mcp::Rect::new(rect.x + ind as f32 * step, rect.y, w, h)
+    }
 
-    col
-}
+    fn render_arena(&self, font: &mcp::Font) {
+        self.arena_rect.render();
+
+        let arena_text = "Arena";
+        let arena_text_width =
+            mcp::measure_text(arena_text, Some(font), self.arena_rect.h as u16, 1.);
+
+        mcp::draw_text_ex(
+            arena_text,
+            self.arena_rect.center().x - arena_text_width.width / 2.0,
+            self.arena_rect.bottom() - 20.0,
+            mcp::TextParams {
+                font: Some(font),
+                font_size: self.arena_rect.h as u16,
+                color: mcp::WHITE,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn render_stats(&self, font: &mcp::Font) {
+        let player = &self.shown;
+        let font_size = self.arena_rect.h / 5.0;
+        let x = SizeRatio::get_x(0.02);
+        let y = self.arena_rect.y + font_size;
+        let stats = format!(
+            "Life {}  Mana {}/{}",
+            player.life, player.mana, player.max_mana
+        );
+
+        mcp::draw_text_ex(
+            &stats,
+            x,
+            y,
+            mcp::TextParams {
+                font: Some(font),
+                font_size: font_size as u16,
+                color: mcp::WHITE,
+                ..Default::default()
+            },
+        );
+
+        if player.shield > 0 {
+            mcp::draw_text_ex(
+                &format!("Shield {}", player.shield),
+                x,
+                y + font_size * 1.2,
+                mcp::TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: mcp::SKYBLUE,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// The top card of `stack`, face down for the library, and how many
+    /// cards are in it. It is outlined under the mouse, as a click opens it.
+    fn render_stack(&self, stack: Stack, font: &mcp::Font) {
+        let rect = self.stack_rect(stack);
+        match (stack, self.pile(stack).last()) {
+            (_, None) => mcp::draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, mcp::GRAY),
+            (Stack::Library, Some(_)) => Renderer::render_card_back(rect),
+            (Stack::Graveyard, Some(card)) => {
+                Renderer::render_card(card, rect, CardLayout::Tiny, font);
+                Shape::draw_rect(rect, mcp::Color::new(0.0, 0.0, 0.0, 0.4));
+            }
+        }
+
+        // the label stays put while the pile bumps
+        let rest = self.resting_rect(stack);
+        if rest.contains(mcp::mouse_position().into()) {
+            mcp::draw_rectangle_lines(rest.x, rest.y, rest.w, rest.h, 3.0, mcp::YELLOW);
+        }
+        let label = match stack {
+            Stack::Library => "Deck",
+            Stack::Graveyard => "Grave",
+        };
+        let font_size = rest.h / 6.0;
+        mcp::draw_text_ex(
+            &format!("{} {}", label, self.pile(stack).len()),
+            rest.x,
+            rest.bottom() + font_size,
+            mcp::TextParams {
+                font: Some(font),
+                font_size: font_size as u16,
+                color: mcp::LIGHTGRAY,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn render_arena_cards(&self, font: &mcp::Font) {
+        for (card, rect) in self.shown.arena.iter().zip(&self.arena_rects) {
+            Renderer::render_card(card, *rect, CardLayout::Tiny, font);
+        }
+    }
+
+    /// Everything but the hand cards.
+    fn render_table(&self, font: &mcp::Font) {
+        self.hand_rect.render();
+        self.render_arena(font);
+        self.render_arena_cards(font);
+        self.render_stack(Stack::Graveyard, font);
+        self.render_stack(Stack::Library, font);
+        self.render_stats(font);
+    }
+
+    /// Render the hand face up. `dragged` is drawn last so it stays on top.
+    pub fn render_hand(&self, font: &mcp::Font, dragged: Option<usize>) {
+        let player = &self.shown;
+        self.render_table(font);
+
+        for (ind, (card, rect)) in player.hand.iter().zip(&self.hand_rects).enumerate() {
+            if Some(ind) != dragged {
+                Renderer::render_card(card, *rect, CardLayout::Minimal, font);
+                if !player.can_afford(card) {
+                    Shape::draw_rect(*rect, mcp::Color::new(0.0, 0.0, 0.0, 0.5));
+                }
+            }
+        }
+
+        if let Some(ind) = dragged
+            && let (Some(card), Some(rect)) = (player.hand.get(ind), self.hand_rects.get(ind))
+        {
+            Renderer::render_card(card, *rect, CardLayout::Minimal, font);
+        }
+    }
+
+    /// Render the hand face down, for the player who is not taking their turn.
+    /// `revealed` is a card being played, it is drawn face up and on top.
+    pub fn render_hidden_hand(&self, font: &mcp::Font, revealed: Option<usize>) {
+        let player = &self.shown;
+        self.render_table(font);
+
+        for (ind, rect) in self.hand_rects.iter().enumerate() {
+            if Some(ind) != revealed {
+                Renderer::render_card_back(*rect);
+            }
+        }
+
+        if let Some(ind) = revealed
+            && let (Some(card), Some(rect)) = (player.hand.get(ind), self.hand_rects.get(ind))
+        {
+            Renderer::render_card(card, *rect, CardLayout::Minimal, font);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_PATH: &str = "opinion-replay.json";
+
+/// What a controller gets to see and touch while it is its player's turn.
+pub struct TurnContext<'a> {
+    pub side: Tern,
+    /// Turns played so far, counting this one.
+    pub turn: u32,
+    pub phase: Phase,
+    /// Whether key presses are meant for the game, false while typing in the chat.
+    pub keyboard: bool,
+    /// The controller's own player, changed only through the returned `GameAction`.
+    pub player: &'a Player,
+    /// Where the player's cards are on screen, free for the controller to move around.
+    pub view: &'a mut PlayerView,
+    pub opponent: &'a Player,
+}
+
+/// Decides the actions of one player, be it a person at the mouse or the computer.
+pub trait Controller {
+    /// Called every frame of the player's turn. Returns an action once one
+    /// is decided, `None` while still waiting.
+    fn poll(&mut self, ctx: TurnContext) -> Option<GameAction>;
+
+    /// Hand card currently being moved on screen, drawn face up and on top.
+    fn moving_card(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether a person plays through this controller on this machine.
+    fn is_local_human(&self) -> bool {
+        false
+    }
+
+    /// Whether the decisions are made on another machine.
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    /// How hard the computer plays, `None` for anything that is not an AI.
+    fn difficulty(&self) -> Option<Difficulty> {
+        None
+    }
+
+    fn name(&self) -> String;
+}
+
+/// Mouse and keyboard input of a person sitting at this screen.
+#[derive(Default)]
+pub struct HumanController {
+    mouse: Mouse,
+    /// Hand cards picked to go under the library during the mulligan, bit
+    /// `i` for hand index `i`.
+    bottom: u32,
+    /// Turn and phase of the last poll. A grab is dropped when they change,
+    /// its release may have happened while the opponent was playing.
+    polled: Option<(u32, Phase)>,
+}
+
+impl HumanController {
+    /// The player asked to leave the current phase, by key or button.
+    fn advance_requested(keyboard: bool) -> bool {
+        if keyboard && mcp::is_key_pressed(mcp::KeyCode::Space) {
+            return true;
+        }
+
+        let (mx, my) = mcp::mouse_position();
+        mcp::is_mouse_button_pressed(mcp::MouseButton::Left)
+            && Game::phase_button_rect().contains(mcp::Vec2::new(mx, my))
+    }
+}
+
+impl HumanController {
+    /// Click hand cards to pick them for the bottom of the library, R
+    /// redraws the whole hand, Space or the button keeps the rest.
+    fn poll_mulligan(
+        &mut self,
+        side: Tern,
+        keyboard: bool,
+        view: &mut PlayerView,
+    ) -> Option<GameAction> {
+        let choice = if keyboard && mcp::is_key_pressed(mcp::KeyCode::R) {
+            Some(Mulligan::Redraw)
+        } else if HumanController::advance_requested(keyboard) {
+            Some(match self.bottom {
+                0 => Mulligan::Keep,
+                cards => Mulligan::Bottom(cards),
+            })
+        } else {
+            None
+        };
+        if let Some(choice) = choice {
+            self.bottom = 0;
+            return Some(GameAction::Mulligan {
+                player: side,
+                choice,
+            });
+        }
+
+        let mouse = mcp::mouse_position().into();
+        if mcp::is_mouse_button_pressed(mcp::MouseButton::Left)
+            && let Some(ind) = view.hand_rects.iter().rposition(|r| r.contains(mouse))
+            && ind < u32::BITS as usize
+        {
+            self.bottom ^= 1 << ind;
+            view.lift_hand_card(ind, self.bottom & (1 << ind) != 0);
+        }
+        None
+    }
+}
+
+impl Controller for HumanController {
+    fn poll(&mut self, ctx: TurnContext) -> Option<GameAction> {
+        let view = ctx.view;
+
+        if self.polled != Some((ctx.turn, ctx.phase)) {
+            self.polled = Some((ctx.turn, ctx.phase));
+            if self.mouse.drop_grab() {
+                view.update_card_position();
+            }
+        }
+
+        if ctx.phase == Phase::Mulligan {
+            return self.poll_mulligan(ctx.side, ctx.keyboard, view);
+        }
+
+        if matches!(ctx.phase, Phase::Main | Phase::Combat)
+            && HumanController::advance_requested(ctx.keyboard)
+        {
+            return Some(GameAction::EndPhase { player: ctx.side });
+        }
+
+        // cards can only be played during the main phase
+        if ctx.phase == Phase::Main {
+            view.update_hand(&mut self.mouse);
+        }
+
+        let released = self.mouse.update(MouseContex {
+            rects: Some(&mut view.hand_rects),
+        });
+
+        if let Some((Obj::Card(ind), Action::Move)) = released {
+            if view.dropped_in_arena(ind) {
+                return Some(GameAction::PlayCard {
+                    player: ctx.side,
+                    hand_index: ind,
+                });
+            }
+            view.update_card_position();
+        }
+
+        None
+    }
+
+    fn moving_card(&self) -> Option<usize> {
+        self.mouse.grabbed_card()
+    }
+
+    fn is_local_human(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> String {
+        "Human".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Difficulty {
+    /// Plays any affordable card and sometimes passes for no reason.
+    Easy,
+    /// Plays the best looking affordable card, one at a time.
+    Normal,
+    /// Also looks for lethal, draws before committing, and spends its mana efficiently.
+    Hard,
+}
+
+impl Difficulty {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+}
+
+/// Seconds the AI waits before each decision, so a person can follow along.
+const AI_THINK_TIME: f64 = 0.6;
+
+/// Dearest card the AI keeps in its opening hand.
+const AI_OPENING_COST: u32 = 3;
+
+/// Computer opponent. It keeps its own RNG so its choices never disturb the
+/// match RNG, its decisions end up in the replay like anyone else's.
+pub struct AiController {
+    difficulty: Difficulty,
+    rng: SimpleRng,
+    /// When the AI last acted, it waits `AI_THINK_TIME` after this.
+    last_action: f64,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        Self {
+            difficulty,
+            rng: SimpleRng::from_seed(seed),
+            last_action: mcp::get_time(),
+        }
+    }
+
+    /// Rough value of playing `card` right now, below zero means not worth it.
+    fn score(card: &Card, me: &Player, opponent: &Player) -> f32 {
+        let opponent_attack: u32 = opponent
+            .arena
+            .iter()
+            .filter(|c| matches!(c.card_type, CardType::Attack))
+            .map(|c| c.power)
+            .sum();
+
+        let mut score = match card.card_type {
+            CardType::Attack => card.power as f32 * 1.5,
+            CardType::Defense => card.power as f32 * if opponent_attack > 0 { 1.5 } else { 0.5 },
+            CardType::Magic | CardType::Support => 0.0,
+        };
+
+        for effect in &card.effects {
+            score += match *effect {
+                Effect::Draw(n) if n as usize >= me.library.len() => -100.0,
+                Effect::Draw(n) => n as f32 * 1.5,
+                Effect::Damage(n) => n as f32 * 2.0,
+                Effect::Heal(n) => n.min(MAX_LIFE - me.life) as f32 * 2.0,
+                Effect::Shield(n) => n as f32 * if opponent_attack > 0 { 1.5 } else { 0.5 },
+                Effect::Discard(n) => n.min(opponent.hand.len() as u32) as f32 * 1.5,
+            };
+        }
+
+        score
+    }
+
+    /// Damage `card` deals to the opponent as soon as it is played.
+    fn direct_damage(card: &Card) -> u32 {
+        card.effects
+            .iter()
+            .map(|e| match *e {
+                Effect::Damage(n) => n,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Cards too dear for the first turns go under the library, a hand of
+    /// nothing else is drawn anew. The easy AI keeps whatever it was dealt.
+    fn choose_mulligan(&self, me: &Player) -> Mulligan {
+        if self.difficulty == Difficulty::Easy {
+            return Mulligan::Keep;
+        }
+
+        let dear = me
+            .hand
+            .iter()
+            .enumerate()
+            .filter(|(ind, card)| card.cost > AI_OPENING_COST && *ind < u32::BITS as usize)
+            .fold(0u32, |cards, (ind, _)| cards | 1 << ind);
+        match dear.count_ones() as usize {
+            0 => Mulligan::Keep,
+            n if n == me.hand.len() => Mulligan::Redraw,
+            _ => Mulligan::Bottom(dear),
+        }
+    }
+
+    /// Hand index of the card to play next, `None` to end the main phase.
+    fn choose_card(&mut self, me: &Player, opponent: &Player) -> Option<usize> {
+        let playable: Vec<usize> = (0..me.hand.len())
+            .filter(|&i| me.can_afford(&me.hand[i]))
+            .collect();
+        if playable.is_empty() {
+            return None;
+        }
+
+        let score = |i: usize| AiController::score(&me.hand[i], me, opponent);
+
+        match self.difficulty {
+            Difficulty::Easy => {
+                // passes a third of the time even when it could play
+                if self.rng.gen_range(0, 3) == 0 {
+                    return None;
+                }
+                Some(playable[self.rng.gen_range(0, playable.len())])
+            }
+            Difficulty::Normal => playable
+                .into_iter()
+                .filter(|&i| score(i) > 0.0)
+                .max_by(|&a, &b| score(a).total_cmp(&score(b))),
+            Difficulty::Hard => {
+                let opponent_life = opponent.life + opponent.shield;
+                if let Some(lethal) = playable
+                    .iter()
+                    .copied()
+                    .find(|&i| AiController::direct_damage(&me.hand[i]) >= opponent_life)
+                {
+                    return Some(lethal);
+                }
+
+                let draws_first = playable.iter().copied().find(|&i| {
+                    score(i) > 0.0
+                        && me.hand[i]
+                            .effects
+                            .iter()
+                            .any(|e| matches!(e, Effect::Draw(_)))
+                });
+
+                // best value for the mana spent, so several cheap cards can beat one big one
+                draws_first.or_else(|| {
+                    playable
+                        .into_iter()
+                        .filter(|&i| score(i) > 0.0)
+                        .max_by(|&a, &b| {
+                            let per_mana = |i: usize| score(i) / me.hand[i].cost.max(1) as f32;
+                            per_mana(a).total_cmp(&per_mana(b))
+                        })
+                })
+            }
+        }
+    }
+}
+
+impl Controller for AiController {
+    fn poll(&mut self, ctx: TurnContext) -> Option<GameAction> {
+        let now = mcp::get_time();
+        if now - self.last_action < AI_THINK_TIME {
+            return None;
+        }
+        self.last_action = now;
+
+        if ctx.phase == Phase::Main
+            && let Some(hand_index) = self.choose_card(ctx.player, ctx.opponent)
+        {
+            return Some(GameAction::PlayCard {
+                player: ctx.side,
+                hand_index,
+            });
+        }
+
+        match ctx.phase {
+            Phase::Mulligan => Some(GameAction::Mulligan {
+                player: ctx.side,
+                choice: self.choose_mulligan(ctx.player),
+            }),
+            Phase::Main | Phase::Combat => Some(GameAction::EndPhase { player: ctx.side }),
+            Phase::Draw | Phase::End => None,
+        }
+    }
+
+    fn difficulty(&self) -> Option<Difficulty> {
+        Some(self.difficulty)
+    }
+
+    fn name(&self) -> String {
+        format!("AI ({:?})", self.difficulty)
+    }
+}
+
+/// Seconds between two actions while watching a replay.
+const PLAYBACK_STEP: f64 = 0.4;
+
+pub struct Game {
+    state: GameState,
+    view1: PlayerView,
+    view2: PlayerView,
+    /// Plays what the rules did on the views, the match waits for it.
+    animator: Animator,
+    /// A pile opened to look through, the match waits while it is open.
+    browsing: Option<PileBrowser>,
+
+    card_collection: CardCollection,
+    controller1: Box<dyn Controller>,
+    controller2: Box<dyn Controller>,
+
+    replay: Replay,
+    /// Actions still to apply while watching a replay, human input is ignored meanwhile.
+    playback: Option<VecDeque<GameAction>>,
+    last_playback_step: f64,
+}
+
+impl Game {
+    /// `decks` are player1's and player2's, `None` deals a random library.
+    pub fn new(
+        card_collection: CardCollection,
+        seed: u64,
+        decks: [Option<Deck>; 2],
+        opening: Opening,
+    ) -> Self {
+        let state = GameState::with_opening(&card_collection, seed, &decks, opening);
+        let mut animator = Animator::default();
+        animator.push(&state.events);
+
+        Self {
+            view1: PlayerView::new(&state.player1, true),
+            view2: PlayerView::new(&state.player2, false),
+            animator,
+            browsing: None,
+            state,
+
+            card_collection,
+            controller1: Box::new(HumanController::default()),
+            controller2: Box::new(AiController::new(Difficulty::Normal, seed.rotate_left(32))),
+
+            replay: Replay::new(seed, decks, opening),
+            playback: None,
+            last_playback_step: 0.0,
+        }
+    }
+
+    /// Match against a player on another machine. Both sides build the game
+    /// from the same seed, random libraries and the classic opening, the
+    /// local player is always drawn at the bottom.
+    pub fn new_online(card_collection: CardCollection, online: &OnlineMatch) -> Self {
+        let mut game = Game::new(
+            card_collection,
+            online.ticket.seed,
+            [None, None],
+            Opening::default(),
+        );
+        let local = online.side();
+        game.set_controller(local, Box::new(HumanController::default()));
+        game.set_controller(local.other(), Box::new(online.remote_controller()));
+
+        game.view1 = PlayerView::new(&game.state.player1, local == Tern::Player1);
+        game.view2 = PlayerView::new(&game.state.player2, local == Tern::Player2);
+        game
+    }
+
+    /// Whether either player is on another machine. Online matches can't be
+    /// restarted or have their players swapped locally.
+    pub fn is_online(&self) -> bool {
+        self.controller1.is_remote() || self.controller2.is_remote()
+    }
+
+    /// Start the recorded match over and play its actions back one by one.
+    pub fn from_replay(card_collection: CardCollection, replay: Replay) -> Self {
+        let mut game = Game::new(card_collection, replay.seed, replay.decks, replay.opening);
+        game.playback = Some(replay.actions.into());
+        game
+    }
+
+    /// Pick a saved local match up where it was left, without animating
+    /// what already happened.
+    pub fn resume(card_collection: CardCollection, saved: SavedMatch) -> Self {
+        let replay = saved.replay;
+        let mut game = Game::new(card_collection, replay.seed, replay.decks, replay.opening);
+        game.set_opponent(saved.opponent);
+        for action in replay.actions {
+            if !game.apply(action) {
+                mcp::warn!("saved match diverged, rejected {:?}", action);
+                break;
+            }
+        }
+
+        game.animator = Animator::default();
+        game.sync_views();
+        game
+    }
+
+    /// Throw the current match away and deal a new one from the same
+    /// collection and decks, keeping who controls which player.
+    pub fn restart(&mut self) {
+        let seed = self.state.rng.next_u64();
+        let card_collection = std::mem::take(&mut self.card_collection);
+        let decks = std::mem::take(&mut self.replay.decks);
+        let mut game = Game::new(card_collection, seed, decks, self.replay.opening);
+        std::mem::swap(&mut game.controller1, &mut self.controller1);
+        std::mem::swap(&mut game.controller2, &mut self.controller2);
+        *self = game;
+    }
+
+    pub fn set_controller(&mut self, side: Tern, controller: Box<dyn Controller>) {
+        match side {
+            Tern::Player1 => self.controller1 = controller,
+            Tern::Player2 => self.controller2 = controller,
+        }
+    }
+
+    /// Cycle player2 through AI difficulties and a second local human.
+    /// The choice is kept for later matches.
+    fn cycle_opponent(&mut self) {
+        let next = match self.controller2.difficulty() {
+            Some(Difficulty::Easy) => Some(Difficulty::Normal),
+            Some(Difficulty::Normal) => Some(Difficulty::Hard),
+            Some(Difficulty::Hard) => None,
+            None => Some(Difficulty::Easy),
+        };
+        self.set_opponent(next);
+
+        let mut settings = Settings::load();
+        settings.opponent = next;
+        settings.save();
+    }
+
+    /// Player2 is the computer at `difficulty`, or a second person at this
+    /// machine for `None`.
+    pub fn set_opponent(&mut self, difficulty: Option<Difficulty>) {
+        // seeded outside the match RNG so switching opponents never changes the deal
+        let seed = SimpleRng::from_entropy().next_u64();
+        let controller: Box<dyn Controller> = match difficulty {
+            Some(difficulty) => Box::new(AiController::new(difficulty, seed)),
+            None => Box::new(HumanController::default()),
+        };
+        self.set_controller(Tern::Player2, controller);
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    fn phase_button_rect() -> mcp::Rect {
+        SizeRatio::new(0.82, 0.465, 0.16, 0.05)
+    }
+
+    /// `keyboard` is false while key presses are meant for something else,
+    /// like the chat input.
+    pub fn update(&mut self, keyboard: bool) {
+        if keyboard && mcp::is_key_pressed(mcp::KeyCode::F5) {
+            self.save_replay();
+        }
+
+        if keyboard && mcp::is_key_pressed(mcp::KeyCode::F2) && !self.is_online() {
+            self.cycle_opponent();
+        }
+
+        self.update_animations();
+
+        if let Some(browser) = &self.browsing {
+            let count = self.view(browser.side()).pile(browser.stack()).len();
+            let open = self
+                .browsing
+                .as_mut()
+                .is_some_and(|browser| browser.update(count, keyboard));
+            if !open {
+                self.browsing = None;
+            }
+            return;
+        }
+
+        if !self.state.is_running {
+            if keyboard && mcp::is_key_pressed(mcp::KeyCode::R) && !self.is_online() {
+                self.restart();
+            }
+            return;
+        }
+
+        // nobody acts on cards that are still on their way
+        if self.is_animating() {
+            return;
+        }
+
+        if let Some(browser) = self.clicked_pile() {
+            self.browsing = Some(browser);
+            return;
+        }
+
+        if self.playback.is_some() {
+            self.update_playback();
+            return;
+        }
+
+        let state = &self.state;
+        let (player, view, opponent, controller) = match state.turn {
+            Tern::Player1 => (
+                &state.player1,
+                &mut self.view1,
+                &state.player2,
+                &mut self.controller1,
+            ),
+            Tern::Player2 => (
+                &state.player2,
+                &mut self.view2,
+                &state.player1,
+                &mut self.controller2,
+            ),
+        };
+
+        let action = controller.poll(TurnContext {
+            side: state.turn,
+            turn: state.turn_count,
+            phase: state.phase,
+            keyboard,
+            player,
+            view,
+            opponent,
+        });
+
+        if let Some(action) = action {
+            if self.apply(action) {
+                self.save_progress();
+            } else {
+                // a card that could not be played goes back in place
+                self.update_card_positions();
+            }
+        }
+    }
+
+    fn view(&self, side: Tern) -> &PlayerView {
+        match side {
+            Tern::Player1 => &self.view1,
+            Tern::Player2 => &self.view2,
+        }
+    }
+
+    /// The library or graveyard just clicked, unless a card is being moved.
+    fn clicked_pile(&self) -> Option<PileBrowser> {
+        if !mcp::is_mouse_button_pressed(mcp::MouseButton::Left)
+            || self.controller1.moving_card().is_some()
+            || self.controller2.moving_card().is_some()
+        {
+            return None;
+        }
+
+        let mouse = mcp::mouse_position().into();
+        [Tern::Player1, Tern::Player2].into_iter().find_map(|side| {
+            [Stack::Library, Stack::Graveyard]
+                .into_iter()
+                .find(|&stack| self.view(side).resting_rect(stack).contains(mouse))
+                .map(|stack| PileBrowser::new(side, stack))
+        })
+    }
+
+    /// Whether a pile is open, it takes Esc for closing it.
+    pub fn is_browsing(&self) -> bool {
+        self.browsing.is_some()
+    }
+
+    /// What `side`'s `stack` shows when opened. A graveyard is public, top
+    /// card first. A library is only shown to the player who would see its
+    /// hand, sorted so the order it is drawn in stays secret.
+    fn pile_contents(&self, side: Tern, stack: Stack) -> PileContents {
+        let cards = self.view(side).pile(stack);
+        match stack {
+            Stack::Graveyard => PileContents::Open(cards.iter().rev().cloned().collect()),
+            Stack::Library if self.hand_visible(side) => {
+                let mut cards = cards.to_vec();
+                cards.sort_by(|a, b| a.cost.cmp(&b.cost).then_with(|| a.name.cmp(&b.name)));
+                PileContents::Open(cards)
+            }
+            Stack::Library => PileContents::Hidden(cards.len()),
+        }
+    }
+
+    /// Store a local match after each move so it can be continued after a
+    /// restart, and forget it once it is over.
+    fn save_progress(&self) {
+        if self.is_online() || self.playback.is_some() {
+            return;
+        }
+        if !self.state.is_running {
+            storage::remove(MATCH_KEY);
+            return;
+        }
+        storage::save(
+            MATCH_KEY,
+            &SavedMatch {
+                replay: self.replay.clone(),
+                opponent: self.controller2.difficulty(),
+            },
+        );
+    }
+
+    fn update_animations(&mut self) {
+        let dt = mcp::get_frame_time();
+        self.view1.update_slides(dt);
+        self.view2.update_slides(dt);
+
+        let face_up = [
+            self.hand_visible(Tern::Player1),
+            self.hand_visible(Tern::Player2),
+        ];
+        if self
+            .animator
+            .update(dt, [&mut self.view1, &mut self.view2], face_up)
+        {
+            // the views caught up with the rules, now make them agree exactly
+            self.sync_views();
+        }
+    }
+
+    fn sync_views(&mut self) {
+        self.view1.shown = self.state.player1.clone();
+        self.view2.shown = self.state.player2.clone();
+        self.update_card_positions();
+    }
+
+    /// Whether cards are still moving on screen. Actions wait until they
+    /// stopped, so every player sees what happened before the next move.
+    pub fn is_animating(&self) -> bool {
+        self.animator.is_busy() || self.view1.is_sliding() || self.view2.is_sliding()
+    }
+
+    fn update_playback(&mut self) {
+        let now = mcp::get_time();
+        if now - self.last_playback_step < PLAYBACK_STEP {
+            return;
+        }
+        self.last_playback_step = now;
+
+        let action = self
+            .playback
+            .as_mut()
+            .and_then(|actions| actions.pop_front());
+        match action {
+            Some(action) => {
+                if !self.apply(action) {
+                    mcp::warn!("replay diverged, rejected {:?}", action);
+                }
+            }
+            // the replay is over, hand control back to the players
+            None => self.playback = None,
+        }
+    }
+
+    /// Apply an action for the active player and record it in the replay.
+    /// Returns false, changing nothing, if the action is not legal right now.
+    pub fn apply(&mut self, action: GameAction) -> bool {
+        if let Err(e) = self.state.apply(action) {
+            mcp::debug!("rejected {:?}: {}", action, e);
+            return false;
+        }
+
+        self.replay.actions.push(action);
+        self.animator.push(&self.state.events);
+        true
+    }
+
+    fn update_card_positions(&mut self) {
+        self.view1.update_card_position();
+        self.view2.update_card_position();
+    }
+
+    /// Write the replay of the current match for bug reports.
+    /// Native builds save it next to the binary, wasm builds print it to the console.
+    fn save_replay(&self) {
+        let json = self.replay.to_json();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match std::fs::write(REPLAY_PATH, &json) {
+            Ok(()) => mcp::info!("replay saved to {}", REPLAY_PATH),
+            Err(e) => mcp::error!("could not save replay to {}: {}", REPLAY_PATH, e),
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        mcp::info!("replay:\n{}", json);
+    }
+
+    /// A hand is shown face up to a local human, and in hotseat games only
+    /// while it is their turn.
+    fn hand_visible(&self, side: Tern) -> bool {
+        let (mine, theirs) = match side {
+            Tern::Player1 => (&self.controller1, &self.controller2),
+            Tern::Player2 => (&self.controller2, &self.controller1),
+        };
+        mine.is_local_human() && (!theirs.is_local_human() || self.state.turn == side)
+    }
+
+    pub fn render(&self, font: &mcp::Font) {
+        // the active player is drawn last so their moving card stays on top
+        let sides = [self.state.turn.other(), self.state.turn];
+        for side in sides {
+            let (view, controller) = match side {
+                Tern::Player1 => (&self.view1, &self.controller1),
+                Tern::Player2 => (&self.view2, &self.controller2),
+            };
+            let moving = if side == self.state.turn {
+                controller.moving_card()
+            } else {
+                None
+            };
+
+            if self.hand_visible(side) {
+                view.render_hand(font, moving);
+            } else {
+                view.render_hidden_hand(font, moving);
+            }
+        }
+
+        self.animator.render(font);
+        self.render_hud(font);
+
+        if let Some(browser) = &self.browsing {
+            let contents = self.pile_contents(browser.side(), browser.stack());
+            browser.render(&contents, font);
+            return;
+        }
+
+        // the match is only over once the last card landed
+        if self.is_animating() {
+            return;
+        }
+        if !self.state.is_running {
+            self.render_game_over(font);
+        } else if let Some(card) = self.hovered_card() {
+            Renderer::render_inspector(card, mcp::mouse_position().into(), font);
+        }
+    }
+
+    /// Card under the mouse pointer, unless a card is being moved.
+    fn hovered_card(&self) -> Option<&Card> {
+        if self.controller1.moving_card().is_some() || self.controller2.moving_card().is_some() {
+            return None;
+        }
+
+        let mouse = mcp::mouse_position().into();
+        [Tern::Player1, Tern::Player2]
+            .into_iter()
+            .find_map(|side| self.view(side).card_at(mouse, self.hand_visible(side)))
+    }
+
+    fn render_game_over(&self, font: &mcp::Font) {
+        let screen = mcp::Rect::new(0.0, 0.0, mcp::screen_width(), mcp::screen_height());
+        Shape::draw_rect(screen, mcp::Color::new(0.0, 0.0, 0.0, 0.75));
+
+        let title = match self.state.winner {
+            Some(winner) => format!("{} wins!", winner),
+            None => "Draw!".to_string(),
+        };
+        let hint = if self.is_online() {
+            "Press Esc for the lobby, Ctrl+Q to quit"
+        } else {
+            "Press R to play again, Esc for the lobby"
+        };
+
+        for (text, font_size, y) in [
+            (
+                title.as_str(),
+                SizeRatio::get_y(0.1),
+                SizeRatio::get_y(0.45),
+            ),
+            (hint, SizeRatio::get_y(0.04), SizeRatio::get_y(0.55)),
+        ] {
+            let dims = mcp::measure_text(text, Some(font), font_size as u16, 1.0);
+            mcp::draw_text_ex(
+                text,
+                screen.center().x - dims.width / 2.0,
+                y,
+                mcp::TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color: mcp::WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// How to take a mulligan, beside the hand of the local player deciding.
+    fn render_mulligan_hint(&self, font: &mcp::Font) {
+        let controller = match self.state.turn {
+            Tern::Player1 => &self.controller1,
+            Tern::Player2 => &self.controller2,
+        };
+        if self.state.phase != Phase::Mulligan
+            || !controller.is_local_human()
+            || self.playback.is_some()
+        {
+            return;
+        }
+
+        let view = self.view(self.state.turn);
+        let font_size = SizeRatio::get_y(0.03);
+        let y = if view.hand_rect.y > view.arena_rect.y {
+            view.hand_rect.y - font_size * 0.4
+        } else {
+            view.hand_rect.bottom() + font_size
+        };
+        mcp::draw_text_ex(
+            "Click cards to put them under your library, R redraws the whole hand, \
+             Space keeps the rest",
+            view.hand_rect.x,
+            y,
+            mcp::TextParams {
+                font: Some(font),
+                font_size: font_size as u16,
+                color: mcp::YELLOW,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn render_hud(&self, font: &mcp::Font) {
+        let font_size = SizeRatio::get_y(0.035);
+        let text_params = mcp::TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color: mcp::WHITE,
+            ..Default::default()
+        };
+
+        let (x, y) = SizeRatio::get(0.02, 0.5);
+        let opponent = match (
+            self.controller1.is_local_human(),
+            self.controller2.is_local_human(),
+        ) {
+            (true, true) => "Hotseat".to_string(),
+            (false, _) => self.controller1.name(),
+            (true, false) => self.controller2.name(),
+        };
+        let mut status = format!(
+            "Turn {} - {} - {} - vs {}",
+            self.state.turn_count, self.state.turn, self.state.phase, opponent
+        );
+        if !self.is_online() {
+            status.push_str(" (F2)");
+        }
+        if self.playback.is_some() {
+            status.push_str(" - REPLAY");
+        }
+        mcp::draw_text_ex(&status, x, y, text_params.clone());
+        self.render_mulligan_hint(font);
+
+        let button = Game::phase_button_rect();
+        let label = match self.state.phase {
+            Phase::Mulligan => "Done",
+            Phase::Combat => "End Turn",
+            _ => "Next Phase",
+        };
+        Shape::draw_rect(button, mcp::DARKGREEN);
+        let dims = mcp::measure_text(label, Some(font), font_size as u16, 1.0);
+        mcp::draw_text_ex(
+            label,
+            button.center().x - dims.width / 2.0,
+            button.center().y + dims.offset_y / 2.0,
+            text_params,
+        );
+    }
+}
+
+/// Load card definitions from a JSON file under `public/assets/`.
+pub async fn load_cards(path: &str) -> Result<CardCollection, CardLoadError> {
+    let text = mcp::load_string(path)
+        .await
+        .map_err(|e| CardLoadError::Io {
+            path: path.to_string(),
+            msg: e.to_string(),
+        })?;
+
+    CardCollection::parse(path, &text)
+}
 
 #[cfg(not(target_arch = "wasm32"))]
 const FONT_PATH: &str = "public/assets/font/JetBrainsMono-Medium.ttf";
@@ -849,36 +2167,558 @@ const FONT_PATH: &str = "public/assets/font/JetBrainsMono-Medium.ttf";
 #[cfg(target_arch = "wasm32")]
 const FONT_PATH: &str = "assets/font/JetBrainsMono-Medium.ttf";
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let card_width = SizeRatio::get_x(0.4);
-    let card_height = SizeRatio::get_y(0.6);
-
-    let fireball = Card::new(
-        CardImage::new(10, 10),
-        "Fireball",
-        "Deals fire damage to enemies.",
-        50,
-        CardType::Magic,
-        mcp::Rect::new(
-            mcp::screen_width() / 2.0 - card_width / 2.0,
-            mcp::screen_height() / 2.0 - card_height / 2.0,
-            card_width,
-            card_height,
-        ),
+#[cfg(not(target_arch = "wasm32"))]
+const CARDS_PATH: &str = "public/assets/cards.json";
+
+#[cfg(target_arch = "wasm32")]
+const CARDS_PATH: &str = "assets/cards.json";
+
+/// What the lobby hands over once the player picked a match.
+pub enum LobbyChoice {
+    Local,
+    Online(OnlineMatch),
+    Editor,
+    DeckBuilder,
+    /// Pick the unfinished local match back up.
+    Resume(SavedMatch),
+}
+
+/// Online menu entry that sent the player to the login form.
+#[derive(Clone, Copy)]
+enum OnlineIntent {
+    Host,
+    Join,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LoginField {
+    Username,
+    Password,
+}
+
+enum LobbyState {
+    Menu,
+    /// Typing a username and password, then waiting for `/api/login` or `/api/register`.
+    Login {
+        username: String,
+        password: String,
+        field: LoginField,
+        /// Create the account instead of logging in to it.
+        register: bool,
+        req: Option<net::HttpRequest>,
+        then: OnlineIntent,
+    },
+    /// Waiting for `/api/creatematch`, then for someone to join.
+    Hosting {
+        req: Option<net::HttpRequest>,
+        online: Option<OnlineMatch>,
+    },
+    /// Typing a match code, then waiting for `/api/joinmatch`.
+    Joining {
+        code: String,
+        req: Option<net::HttpRequest>,
+    },
+}
+
+/// Start screen: play locally, host an online match or join one by code.
+/// Online play needs an account, the player is asked to log in on the way.
+pub struct Lobby {
+    state: LobbyState,
+    session: Option<Session>,
+    /// Last problem, shown under the menu.
+    error: Option<String>,
+    /// The local match left unfinished last time, if any.
+    saved: Option<SavedMatch>,
+    settings: Settings,
+}
+
+/// Length of the codes handed out by `/api/creatematch`.
+const MATCH_CODE_LEN: usize = 6;
+
+/// Limits of `/api/register`, checked here too so typos show up right away.
+const USERNAME_MAX_LEN: usize = 20;
+const PASSWORD_MAX_LEN: usize = 128;
+
+impl Lobby {
+    pub fn new(session: Option<Session>) -> Self {
+        Self {
+            state: LobbyState::Menu,
+            session,
+            error: None,
+            saved: storage::load(MATCH_KEY),
+            settings: Settings::load(),
+        }
+    }
+
+    /// Log in or out, remembering it for the next start.
+    fn set_session(&mut self, session: Option<Session>) {
+        match &session {
+            Some(session) => storage::save(SESSION_KEY, session),
+            None => storage::remove(SESSION_KEY),
+        }
+        self.session = session;
+    }
+
+    /// Change how local matches are dealt from the menu.
+    fn update_opening(&mut self) {
+        let opening = &mut self.settings.opening;
+        let before = *opening;
+        if mcp::is_key_pressed(mcp::KeyCode::H) {
+            opening.hand_size = opening.hand_size % MAX_HAND_SIZE + 1;
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::M) {
+            opening.mulligan = !opening.mulligan;
+        }
+        if mcp::is_key_pressed(mcp::KeyCode::G) {
+            opening.compensation = match opening.compensation {
+                Compensation::None => Compensation::ExtraCard,
+                Compensation::ExtraCard => Compensation::ExtraMana,
+                Compensation::ExtraMana => Compensation::None,
+            };
+        }
+        if *opening != before {
+            self.settings.save();
+        }
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Session of a player who got past the login form.
+    fn logged_in(&self) -> Session {
+        self.session
+            .clone()
+            .expect("online menus are only reachable with a session")
+    }
+
+    /// Go on to hosting or joining, through the login form if needed.
+    fn go_online(&mut self, then: OnlineIntent) {
+        self.error = None;
+        self.state = match (&self.session, then) {
+            (None, _) => LobbyState::Login {
+                username: self.settings.username.clone(),
+                password: String::new(),
+                field: LoginField::Username,
+                register: false,
+                req: None,
+                then,
+            },
+            (Some(session), OnlineIntent::Host) => LobbyState::Hosting {
+                req: Some(net::HttpRequest::post_as(
+                    session,
+                    "/api/creatematch",
+                    &serde_json::json!({}),
+                )),
+                online: None,
+            },
+            (Some(_), OnlineIntent::Join) => LobbyState::Joining {
+                code: String::new(),
+                req: None,
+            },
+        };
+    }
+
+    /// Parse a successful JSON reply, or remember why the request failed.
+    /// A rejected session is forgotten so the player is asked to log in again.
+    fn reply<T: serde::de::DeserializeOwned>(
+        &mut self,
+        result: Result<net::HttpResponse, String>,
+    ) -> Option<T> {
+        let parsed = match result {
+            Ok(resp) if resp.is_success() => {
+                serde_json::from_str(&resp.body).map_err(|e| format!("bad reply: {}", e))
+            }
+            Ok(resp) => {
+                if resp.status == 401 {
+                    self.set_session(None);
+                }
+                Err(resp.error_message())
+            }
+            Err(e) => Err(e),
+        };
+
+        match parsed {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.error = Some(e);
+                self.state = LobbyState::Menu;
+                None
+            }
+        }
+    }
+
+    pub fn update(&mut self) -> Option<LobbyChoice> {
+        if mcp::is_key_pressed(mcp::KeyCode::Escape) {
+            self.state = LobbyState::Menu;
+        }
+
+        match &mut self.state {
+            LobbyState::Menu => {
+                if mcp::is_key_pressed(mcp::KeyCode::Key1) {
+                    return Some(LobbyChoice::Local);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Key2) {
+                    self.go_online(OnlineIntent::Host);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Key3) {
+                    self.go_online(OnlineIntent::Join);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Key4) {
+                    return Some(LobbyChoice::Editor);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Key5) {
+                    return Some(LobbyChoice::DeckBuilder);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Key6) && self.session.is_some() {
+                    self.set_session(None);
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::C)
+                    && let Some(saved) = self.saved.take()
+                {
+                    return Some(LobbyChoice::Resume(saved));
+                }
+                self.update_opening();
+                // swallow the digit typed to get here
+                while mcp::get_char_pressed().is_some() {}
+            }
+            LobbyState::Login {
+                username,
+                password,
+                field,
+                register,
+                req,
+                then,
+            } => {
+                if let Some(r) = req {
+                    if let Some(result) = r.poll() {
+                        let then = *then;
+                        let session = self.reply::<Session>(result)?;
+                        self.settings.username = session.username.clone();
+                        self.settings.save();
+                        self.set_session(Some(session));
+                        self.go_online(then);
+                    }
+                    return None;
+                }
+
+                while let Some(ch) = mcp::get_char_pressed() {
+                    match field {
+                        LoginField::Username => {
+                            if (ch.is_ascii_alphanumeric() || ch == '_')
+                                && username.len() < USERNAME_MAX_LEN
+                            {
+                                username.push(ch.to_ascii_lowercase());
+                            }
+                        }
+                        LoginField::Password => {
+                            if !ch.is_control() && password.chars().count() < PASSWORD_MAX_LEN {
+                                password.push(ch);
+                            }
+                        }
+                    }
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Backspace) {
+                    match field {
+                        LoginField::Username => username.pop(),
+                        LoginField::Password => password.pop(),
+                    };
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::F1) {
+                    *register = !*register;
+                }
+
+                let enter = mcp::is_key_pressed(mcp::KeyCode::Enter);
+                if mcp::is_key_pressed(mcp::KeyCode::Tab)
+                    || (enter && *field == LoginField::Username)
+                {
+                    *field = match field {
+                        LoginField::Username => LoginField::Password,
+                        LoginField::Password => LoginField::Username,
+                    };
+                } else if enter && !username.is_empty() && !password.is_empty() {
+                    let path = if *register {
+                        "/api/register"
+                    } else {
+                        "/api/login"
+                    };
+                    *req = Some(net::HttpRequest::post(
+                        path,
+                        &serde_json::json!({ "username": username, "password": password }),
+                    ));
+                }
+            }
+            LobbyState::Hosting { req, online } => {
+                if let Some(result) = req.as_mut().and_then(|r| r.poll()) {
+                    *req = None;
+                    if let Some(ticket) = self.reply::<net::MatchTicket>(result) {
+                        self.state = LobbyState::Hosting {
+                            req: None,
+                            online: Some(OnlineMatch::new(ticket, self.logged_in())),
+                        };
+                    }
+                } else if let Some(match_) = online {
+                    match_.update(&[]);
+                    if match_.started {
+                        let LobbyState::Hosting {
+                            online: Some(match_),
+                            ..
+                        } = std::mem::replace(&mut self.state, LobbyState::Menu)
+                        else {
+                            unreachable!();
+                        };
+                        return Some(LobbyChoice::Online(match_));
+                    }
+                }
+            }
+            LobbyState::Joining { code, req } => {
+                if let Some(r) = req {
+                    if let Some(result) = r.poll() {
+                        let ticket = self.reply::<net::MatchTicket>(result)?;
+                        self.state = LobbyState::Menu;
+                        let mut match_ = OnlineMatch::new(ticket, self.logged_in());
+                        match_.started = true;
+                        return Some(LobbyChoice::Online(match_));
+                    }
+                    return None;
+                }
+
+                while let Some(ch) = mcp::get_char_pressed() {
+                    if ch.is_ascii_alphanumeric() && code.len() < MATCH_CODE_LEN {
+                        code.push(ch.to_ascii_uppercase());
+                    }
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Backspace) {
+                    code.pop();
+                }
+                if mcp::is_key_pressed(mcp::KeyCode::Enter) && code.len() == MATCH_CODE_LEN {
+                    let body = serde_json::json!({ "match_id": code });
+                    *req = Some(net::HttpRequest::post_as(
+                        self.session.as_ref()?,
+                        "/api/joinmatch",
+                        &body,
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn render(&self, font: &mcp::Font) {
+        let lines: Vec<String> = match &self.state {
+            LobbyState::Menu => {
+                let mut lines = vec![
+                    "Opinion".to_string(),
+                    "1 - Play on this machine".to_string(),
+                    "2 - Host an online match".to_string(),
+                    "3 - Join an online match".to_string(),
+                    "4 - Edit card art".to_string(),
+                    "5 - Build your deck".to_string(),
+                ];
+                if self.saved.is_some() {
+                    lines.push("C - Continue the unfinished match".to_string());
+                }
+                let opening = &self.settings.opening;
+                lines.push(format!("H - Opening hand: {} cards", opening.hand_size));
+                lines.push(format!(
+                    "M - Mulligan: {}",
+                    if opening.mulligan { "on" } else { "off" }
+                ));
+                lines.push(format!("G - Going second gets {}", opening.compensation));
+                if let Some(session) = &self.session {
+                    lines.push("6 - Log out".to_string());
+                    lines.push(format!("Logged in as {}", session.username));
+                }
+                lines
+            }
+            LobbyState::Login {
+                username,
+                password,
+                field,
+                register,
+                req,
+                ..
+            } => {
+                let cursor = |f| if *field == f { "_" } else { "" };
+                match req {
+                    Some(_) if *register => vec!["Creating account...".to_string()],
+                    Some(_) => vec!["Logging in...".to_string()],
+                    None => vec![
+                        if *register {
+                            "Create an account"
+                        } else {
+                            "Log in"
+                        }
+                        .to_string(),
+                        format!("Username: {}{}", username, cursor(LoginField::Username)),
+                        format!(
+                            "Password: {}{}",
+                            "*".repeat(password.chars().count()),
+                            cursor(LoginField::Password)
+                        ),
+                        format!(
+                            "Tab - Next field   F1 - {}   Esc - Back",
+                            if *register {
+                                "I have an account"
+                            } else {
+                                "New account"
+                            }
+                        ),
+                    ],
+                }
+            }
+            LobbyState::Hosting { online: None, .. } => vec!["Creating match...".to_string()],
+            LobbyState::Hosting {
+                online: Some(match_),
+                ..
+            } => vec![
+                format!("Match code: {}", match_.ticket.match_id),
+                "Waiting for an opponent to join...".to_string(),
+                "Esc - Back".to_string(),
+            ],
+            LobbyState::Joining { code, req: None } => vec![
+                "Enter match code:".to_string(),
+                format!("{}_", code),
+                "Enter - Join   Esc - Back".to_string(),
+            ],
+            LobbyState::Joining { req: Some(_), .. } => vec!["Joining match...".to_string()],
+        };
+
+        // the menu has grown long enough to start high up
+        let font_size = SizeRatio::get_y(0.045);
+        let mut y = SizeRatio::get_y(0.15);
+        for line in lines.iter().chain(self.error.iter()) {
+            let color = if Some(line) == self.error.as_ref() {
+                mcp::RED
+            } else {
+                mcp::WHITE
+            };
+            mcp::draw_text_ex(
+                line,
+                SizeRatio::get_x(0.1),
+                y,
+                mcp::TextParams {
+                    font: Some(font),
+                    font_size: font_size as u16,
+                    color,
+                    ..Default::default()
+                },
+            );
+            y += font_size * 1.35;
+        }
+    }
+}
+
+/// What the main loop is currently showing.
+enum Scene {
+    Lobby(Lobby),
+    Playing {
+        game: Box<Game>,
+        online: Option<OnlineMatch>,
+        chat: ChatPanel,
+        /// Handed back to the lobby when the match is left.
+        session: Option<Session>,
+    },
+    Editor {
+        editor: Box<CardEditor>,
+        session: Option<Session>,
+    },
+    DeckBuilder {
+        builder: Box<DeckBuilder>,
+        session: Option<Session>,
+    },
+}
+
+impl Scene {
+    /// Online matches chat in a room named after the match code, local ones in the lobby.
+    fn playing(game: Game, online: Option<OnlineMatch>, session: Option<Session>) -> Self {
+        let room = match &online {
+            Some(online) => online.ticket.match_id.as_str(),
+            None => "lobby",
+        };
+        Scene::Playing {
+            chat: ChatPanel::new(room, session.clone()),
+            game: Box::new(game),
+            online,
+            session,
+        }
+    }
+}
+
+/// A match on this machine against the stored opponent and with the stored
+/// opening, with the selected deck if it is legal.
+fn local_game(card_collection: &CardCollection, decks: &DeckList) -> Game {
+    let settings = Settings::load();
+    let mut game = Game::new(
+        card_collection.clone(),
+        SimpleRng::from_entropy().next_u64(),
+        [decks.playable(card_collection), None],
+        settings.opening,
     );
+    game.set_opponent(settings.opponent);
+    game
+}
 
-    println!("{}", fireball);
+/// `--replay <file>` on the command line watches a saved replay instead of
+/// starting a new match. Not available on wasm.
+fn startup_replay() -> Option<Replay> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut args = std::env::args().skip_while(|a| a != "--replay").skip(1);
+        let path = args.next()?;
+        match std::fs::read_to_string(&path).map(|text| Replay::from_json(&text)) {
+            Ok(Ok(replay)) => return Some(replay),
+            Ok(Err(e)) => mcp::error!("{}: invalid replay: {}", path, e),
+            Err(e) => mcp::error!("{}: could not read replay: {}", path, e),
+        }
+    }
 
-    let mut cards = vec![fireball];
+    None
+}
 
-    let mut card_collection = CardCollection::generate();
+/// Show a fatal startup error until the window is closed.
+async fn show_error(msg: &str) {
+    mcp::error!("{}", msg);
 
-    let mut player1 = Player::new(&mut card_collection);
+    loop {
+        mcp::clear_background(mcp::Color::from_rgba(31, 31, 31, 255));
 
-    let font = mcp::load_ttf_font(FONT_PATH).await.unwrap();
+        if should_quit() {
+            break;
+        }
+
+        let margin = SizeRatio::get_x(0.05);
+        let rect = mcp::Rect::new(
+            margin,
+            margin,
+            mcp::screen_width() - margin * 2.0,
+            mcp::screen_height() - margin * 2.0,
+        );
+        draw_text_in_rect(msg, rect, 24, mcp::RED);
 
-    let mut mouse = Mouse::new();
+        mcp::next_frame().await
+    }
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    let mut card_collection = match load_cards(CARDS_PATH).await {
+        Ok(col) => col,
+        Err(e) => return show_error(&e.to_string()).await,
+    };
+    let mut decks = DeckList::load();
+
+    let mut scene = match startup_replay() {
+        Some(replay) => Scene::playing(
+            Game::from_replay(card_collection.clone(), replay),
+            None,
+            None,
+        ),
+        None => Scene::Lobby(Lobby::new(storage::load(SESSION_KEY))),
+    };
+
+    let font = mcp::load_ttf_font(FONT_PATH).await.unwrap();
 
     loop {
         mcp::clear_background(mcp::Color::from_rgba(31, 31, 31, 255));
@@ -892,22 +2732,104 @@ async fn main() {
         //     .close_button(true)
         //     .ui(&mut root_ui(), |ui| {});
 
-        //update
-        // for (ind, card) in cards.iter_mut().enumerate() {
-        //     card.update(&mut mouse, ind);
-        // }
-        //
-        // for card in cards.iter() {
-        //     Renderer::render_card(&card, &font);
-        // }
-
-        player1.render_hand(&font);
-
-        let mouse_contex = MouseContex {
-            cards: Some(&mut cards),
+        let next = match &mut scene {
+            Scene::Lobby(lobby) => {
+                let choice = lobby.update();
+                lobby.render(&font);
+
+                let session = lobby.session().cloned();
+                match choice {
+                    Some(LobbyChoice::Local) => Some(Scene::playing(
+                        local_game(&card_collection, &decks),
+                        None,
+                        session,
+                    )),
+                    Some(LobbyChoice::Resume(saved)) => Some(Scene::playing(
+                        Game::resume(card_collection.clone(), saved),
+                        None,
+                        session,
+                    )),
+                    Some(LobbyChoice::Online(online)) => Some(Scene::playing(
+                        Game::new_online(card_collection.clone(), &online),
+                        Some(online),
+                        session,
+                    )),
+                    Some(LobbyChoice::Editor) => match CardEditor::load(CARDS_PATH).await {
+                        Ok(editor) => Some(Scene::Editor {
+                            editor: Box::new(editor),
+                            session,
+                        }),
+                        Err(e) => {
+                            lobby.error = Some(e.to_string());
+                            None
+                        }
+                    },
+                    Some(LobbyChoice::DeckBuilder) => Some(Scene::DeckBuilder {
+                        builder: Box::new(DeckBuilder::new(card_collection.clone(), decks.clone())),
+                        session,
+                    }),
+                    None => None,
+                }
+            }
+            Scene::Playing {
+                game,
+                online,
+                chat,
+                session,
+            } => {
+                // checked before the chat so the Esc that closes it, or a pile,
+                // stays in the match
+                let leave = !chat.is_typing()
+                    && !game.is_browsing()
+                    && mcp::is_key_pressed(mcp::KeyCode::Escape);
+                chat.update();
+                game.update(!chat.is_typing());
+                if let Some(online) = online {
+                    online.update(&game.replay().actions);
+                }
+                game.render(&font);
+                // a desync ends the match for good, it outranks passing errors
+                let problem = online
+                    .as_ref()
+                    .and_then(|o| o.desync.as_ref().or(o.error.as_ref()));
+                if let Some(err) = problem {
+                    mcp::draw_text(err, 20.0, mcp::screen_height() - 10.0, 20.0, mcp::RED);
+                }
+                chat.render(&font);
+
+                leave.then(|| Scene::Lobby(Lobby::new(session.take())))
+            }
+            Scene::Editor { editor, session } => {
+                let leave = editor.update().await;
+                editor.render(&font);
+
+                // later matches use the new art, saved or not
+                leave.then(|| {
+                    card_collection = editor.collection().clone();
+                    Scene::Lobby(Lobby::new(session.take()))
+                })
+            }
+            Scene::DeckBuilder { builder, session } => {
+                let exit = builder.update();
+                builder.render(&font);
+
+                exit.map(|exit| {
+                    decks = builder.decks().clone();
+                    match exit {
+                        DeckBuilderExit::Back => Scene::Lobby(Lobby::new(session.take())),
+                        DeckBuilderExit::Play => Scene::playing(
+                            local_game(&card_collection, &decks),
+                            None,
+                            session.take(),
+                        ),
+                    }
+                })
+            }
         };
+        if let Some(next) = next {
+            scene = next;
+        }
 
-        mouse.update(mouse_contex);
         draw_fps();
         mcp::next_frame().await
     }
@@ -925,7 +2847,7 @@ pub enum Obj {
 }
 
 pub struct MouseContex<'a> {
-    cards: Option<&'a mut Vec<Card>>,
+    rects: Option<&'a mut Vec<mcp::Rect>>,
 }
 
 trait Grabbable {
@@ -937,6 +2859,12 @@ pub struct Mouse {
     grab: Option<(Obj, Action)>,
 }
 
+impl Default for Mouse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Mouse {
     pub fn new() -> Self {
         Self {
@@ -945,24 +2873,37 @@ impl Mouse {
         }
     }
 
-    pub fn update(&mut self, ctx: MouseContex) {
+    /// Apply the current grab to the objects in `ctx`.
+    /// Returns the grab that was let go of this frame, if any.
+    pub fn update(&mut self, ctx: MouseContex) -> Option<(Obj, Action)> {
         let delta = self.delta();
+        let mut released = None;
 
-        if let Some((grab, act)) = self.grab {
-            match (grab, ctx.cards) {
-                (Obj::Card(ind), Some(cards)) => match act {
-                    Action::Resize(edge) => cards[ind].resize(delta, edge),
-                    Action::Move => cards[ind].move_to(mcp::mouse_position()),
-                },
-                (_, _) => {}
+        if let Some((Obj::Card(ind), act)) = self.grab
+            && let Some(rects) = ctx.rects
+        {
+            match (act, rects.get_mut(ind)) {
+                (Action::Resize(edge), Some(rect)) => rect.resize(delta, &edge),
+                (Action::Move, Some(rect)) => rect.move_to(mcp::mouse_position().into()),
+                // the card left the row while it was held
+                (_, None) => self.grab = None,
             }
         }
 
         if mcp::is_mouse_button_released(mcp::MouseButton::Left) {
-            self.grab = None;
+            released = self.grab.take();
         }
 
         self.last_pos = mcp::mouse_position();
+        released
+    }
+
+    /// Index of the card currently being dragged, if any.
+    pub fn grabbed_card(&self) -> Option<usize> {
+        match self.grab {
+            Some((Obj::Card(ind), Action::Move)) => Some(ind),
+            _ => None,
+        }
     }
 
     pub fn delta(&mut self) -> (f32, f32) {
@@ -973,6 +2914,12 @@ impl Mouse {
         (dx, dy)
     }
 
+    /// Let go of the current grab without a release. Returns whether
+    /// something was held.
+    pub fn drop_grab(&mut self) -> bool {
+        self.grab.take().is_some()
+    }
+
     pub fn grab_it(&mut self, obj: Obj, act: Action) -> bool {
         if self.grab.is_some() {
             return false;
@@ -0,0 +1,452 @@
+//! Talking to the Vercel API: a non-blocking HTTP request that works on both
+//! native and wasm, and the online match built on top of it.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use macroquad::prelude as mcp;
+use serde::{Deserialize, Serialize};
+
+use crate::{Controller, GameAction, Tern, TurnContext};
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    fn http_send(
+        method: *const u8,
+        method_len: usize,
+        url: *const u8,
+        url_len: usize,
+        body: *const u8,
+        body_len: usize,
+        token: *const u8,
+        token_len: usize,
+    ) -> u32;
+    fn http_status(id: u32) -> i32;
+    fn http_body_len(id: u32) -> usize;
+    fn http_body_read(id: u32, dst: *mut u8);
+}
+
+/// Where the API lives. The wasm build is served by the same Vercel
+/// deployment, native builds read `OPINION_API_URL`.
+fn api_url(path: &str) -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let base = std::env::var("OPINION_API_URL")
+            .unwrap_or_else(|_| "http://localhost:3000".to_string());
+        format!("{}{}", base.trim_end_matches('/'), path)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        path.to_string()
+    }
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// The `error` field of an API error reply, or the raw body.
+    pub fn error_message(&self) -> String {
+        #[derive(Deserialize)]
+        struct ApiError {
+            error: String,
+        }
+
+        serde_json::from_str::<ApiError>(&self.body)
+            .map(|e| e.error)
+            .unwrap_or_else(|_| format!("HTTP {}: {}", self.status, self.body))
+    }
+}
+
+/// A request in flight. Poll it every frame, rendering never waits on the network.
+pub struct HttpRequest {
+    #[cfg(not(target_arch = "wasm32"))]
+    rx: std::sync::mpsc::Receiver<Result<HttpResponse, String>>,
+    #[cfg(target_arch = "wasm32")]
+    id: u32,
+}
+
+impl HttpRequest {
+    /// Send a JSON request to `path` on the API, e.g. `/api/getmsg`,
+    /// authenticated with the session `token` if there is one.
+    pub fn send(method: &str, path: &str, body: Option<String>, token: Option<&str>) -> Self {
+        let url = api_url(path);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let method = method.to_string();
+            let auth = token.map(|t| format!("Bearer {}", t));
+            std::thread::spawn(move || {
+                let mut req = ureq::request(&method, &url).set("Content-Type", "application/json");
+                if let Some(auth) = &auth {
+                    req = req.set("Authorization", auth);
+                }
+                let resp = match body {
+                    Some(body) => req.send_string(&body),
+                    None => req.call(),
+                };
+                let result = match resp {
+                    Ok(resp) | Err(ureq::Error::Status(_, resp)) => {
+                        let status = resp.status();
+                        resp.into_string()
+                            .map(|body| HttpResponse { status, body })
+                            .map_err(|e| e.to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                let _ = tx.send(result);
+            });
+            HttpRequest { rx }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let body = body.unwrap_or_default();
+            let token = token.unwrap_or_default();
+            let id = unsafe {
+                http_send(
+                    method.as_ptr(),
+                    method.len(),
+                    url.as_ptr(),
+                    url.len(),
+                    body.as_ptr(),
+                    body.len(),
+                    token.as_ptr(),
+                    token.len(),
+                )
+            };
+            HttpRequest { id }
+        }
+    }
+
+    pub fn get(path: &str) -> Self {
+        HttpRequest::send("GET", path, None, None)
+    }
+
+    pub fn post(path: &str, body: &serde_json::Value) -> Self {
+        HttpRequest::send("POST", path, Some(body.to_string()), None)
+    }
+
+    /// POST on behalf of the logged in player.
+    pub fn post_as(session: &Session, path: &str, body: &serde_json::Value) -> Self {
+        HttpRequest::send("POST", path, Some(body.to_string()), Some(&session.token))
+    }
+
+    /// `None` while the request is in flight, then its outcome exactly once.
+    /// `Err` means the server could not be reached at all.
+    pub fn poll(&mut self) -> Option<Result<HttpResponse, String>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            match self.rx.try_recv() {
+                Ok(result) => Some(result),
+                Err(std::sync::mpsc::TryRecvError::Empty) => None,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    Some(Err("request was dropped".to_string()))
+                }
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let status = unsafe { http_status(self.id) };
+            if status == 0 {
+                return None;
+            }
+
+            let len = unsafe { http_body_len(self.id) };
+            let mut buf = vec![0u8; len];
+            unsafe { http_body_read(self.id, buf.as_mut_ptr()) };
+            if status < 0 {
+                return Some(Err("network error".to_string()));
+            }
+            Some(Ok(HttpResponse {
+                status: status as u16,
+                body: String::from_utf8_lossy(&buf).into_owned(),
+            }))
+        }
+    }
+}
+
+/// Reply of `/api/login` and `/api/register`: who the player is logged in as.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub username: String,
+    pub token: String,
+}
+
+/// Seconds between two polls of the match state.
+const POLL_INTERVAL: f64 = 1.0;
+
+/// Reply of `/api/creatematch` and `/api/joinmatch`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatchTicket {
+    pub match_id: String,
+    pub side: Tern,
+    pub seed: u64,
+}
+
+/// Reply of `/api/getmatch`.
+#[derive(Debug, Deserialize)]
+struct MatchState {
+    status: String,
+    since: usize,
+    actions: Vec<GameAction>,
+}
+
+/// What to do after the server answered the send of action `seq`.
+#[derive(Debug, PartialEq, Eq)]
+enum SendOutcome {
+    /// The server has the action, go on with the next one.
+    Accepted,
+    /// The action may not have arrived, send it again later.
+    Retry(String),
+    /// The server will never take the action, the two copies of the match
+    /// went apart.
+    Desync(String),
+}
+
+fn send_outcome(seq: usize, result: Result<HttpResponse, String>) -> SendOutcome {
+    #[derive(Deserialize)]
+    struct OutOfSync {
+        action_count: usize,
+    }
+
+    let resp = match result {
+        Ok(resp) if resp.is_success() => return SendOutcome::Accepted,
+        Ok(resp) => resp,
+        Err(e) => return SendOutcome::Retry(e),
+    };
+    match resp.status {
+        // the action was rejected by the rules, sending it again can't help
+        422 => SendOutcome::Desync(resp.error_message()),
+        409 => match serde_json::from_str::<OutOfSync>(&resp.body) {
+            // an earlier send got through but its reply was lost
+            Ok(conflict) if conflict.action_count > seq => SendOutcome::Accepted,
+            Ok(conflict) if conflict.action_count < seq => SendOutcome::Desync(format!(
+                "out of sync: the server has {} actions, ours is number {}",
+                conflict.action_count,
+                seq + 1
+            )),
+            // lost a race with the other writer, the next send will tell
+            _ => SendOutcome::Retry(resp.error_message()),
+        },
+        _ => SendOutcome::Retry(resp.error_message()),
+    }
+}
+
+/// Actions received from the server for the remote player, in order.
+type Inbox = Rc<RefCell<VecDeque<GameAction>>>;
+
+/// Keeps a local `Game` and the server's copy of a match in step. The server
+/// holds the seed and the action list, so each side only has to send its own
+/// actions and replay the other side's.
+pub struct OnlineMatch {
+    pub ticket: MatchTicket,
+    session: Session,
+    inbox: Inbox,
+    /// Actions of the server list already seen.
+    known: usize,
+    /// Index in the local action list of the next action to look at for sending.
+    sent: usize,
+    /// The opponent has joined and the match is on.
+    pub started: bool,
+    poll_req: Option<HttpRequest>,
+    send_req: Option<(usize, HttpRequest)>,
+    /// After a failed send, wait until then before sending again.
+    send_retry_at: f64,
+    last_poll: f64,
+    /// Last problem talking to the server, shown to the player.
+    pub error: Option<String>,
+    /// Why the server stopped taking our actions. Nothing is sent any more.
+    pub desync: Option<String>,
+}
+
+impl OnlineMatch {
+    pub fn new(ticket: MatchTicket, session: Session) -> Self {
+        Self {
+            ticket,
+            session,
+            inbox: Rc::default(),
+            known: 0,
+            sent: 0,
+            started: false,
+            poll_req: None,
+            send_req: None,
+            send_retry_at: f64::MIN,
+            last_poll: f64::MIN,
+            error: None,
+            desync: None,
+        }
+    }
+
+    pub fn side(&self) -> Tern {
+        self.ticket.side
+    }
+
+    /// Controller for the player on the other side of the network.
+    pub fn remote_controller(&self) -> RemoteController {
+        RemoteController {
+            inbox: self.inbox.clone(),
+        }
+    }
+
+    /// Send new local actions and fetch new remote ones. `actions` is every
+    /// action applied to the local game so far, in order.
+    pub fn update(&mut self, actions: &[GameAction]) {
+        self.update_send(actions);
+        self.update_poll();
+    }
+
+    fn update_send(&mut self, actions: &[GameAction]) {
+        let now = mcp::get_time();
+
+        if let Some((seq, req)) = &mut self.send_req {
+            let seq = *seq;
+            let Some(result) = req.poll() else {
+                return;
+            };
+            match send_outcome(seq, result) {
+                SendOutcome::Accepted => {
+                    self.sent = seq + 1;
+                    self.error = None;
+                }
+                SendOutcome::Retry(e) => {
+                    self.error = Some(e);
+                    self.send_retry_at = now + POLL_INTERVAL;
+                }
+                SendOutcome::Desync(e) => self.desync = Some(e),
+            }
+            self.send_req = None;
+        }
+
+        if self.desync.is_some() || now < self.send_retry_at {
+            return;
+        }
+
+        // remote actions came from the server, only our own need sending
+        while self.sent < actions.len() && actions[self.sent].player() != self.side() {
+            self.sent += 1;
+        }
+
+        if let Some(action) = actions.get(self.sent) {
+            let body = serde_json::json!({
+                "match_id": self.ticket.match_id,
+                "seq": self.sent,
+                "action": action,
+            });
+            self.send_req = Some((
+                self.sent,
+                HttpRequest::post_as(&self.session, "/api/sendaction", &body),
+            ));
+        }
+    }
+
+    fn update_poll(&mut self) {
+        if let Some(req) = &mut self.poll_req {
+            match req.poll() {
+                None => return,
+                Some(Ok(resp)) if resp.is_success() => match serde_json::from_str(&resp.body) {
+                    Ok(state) => self.receive(state),
+                    Err(e) => self.error = Some(format!("bad match state: {}", e)),
+                },
+                Some(Ok(resp)) => self.error = Some(resp.error_message()),
+                Some(Err(e)) => self.error = Some(e),
+            }
+            self.poll_req = None;
+        }
+
+        let now = mcp::get_time();
+        if now - self.last_poll >= POLL_INTERVAL {
+            self.last_poll = now;
+            let path = format!(
+                "/api/getmatch?match_id={}&since={}",
+                self.ticket.match_id, self.known
+            );
+            self.poll_req = Some(HttpRequest::get(&path));
+        }
+    }
+
+    fn receive(&mut self, state: MatchState) {
+        self.started = state.status == "playing";
+
+        for (ind, action) in state.actions.into_iter().enumerate() {
+            let index = state.since + ind;
+            if index < self.known {
+                continue;
+            }
+            if action.player() != self.side() {
+                self.inbox.borrow_mut().push_back(action);
+            }
+            self.known = index + 1;
+        }
+    }
+}
+
+/// Plays the actions the opponent made on their own machine.
+pub struct RemoteController {
+    inbox: Inbox,
+}
+
+impl Controller for RemoteController {
+    fn poll(&mut self, _ctx: TurnContext) -> Option<GameAction> {
+        self.inbox.borrow_mut().pop_front()
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn name(&self) -> String {
+        "Online".to_string()
+    }
+}
+
+#[test]
+fn send_outcome_test() {
+    let reply = |status, body: &str| {
+        Ok(HttpResponse {
+            status,
+            body: body.to_string(),
+        })
+    };
+
+    assert_eq!(
+        send_outcome(3, reply(200, r#"{"action_count":4}"#)),
+        SendOutcome::Accepted
+    );
+    // the first send of action 3 was stored but its reply got lost
+    assert_eq!(
+        send_outcome(3, reply(409, r#"{"error":"out of sync","action_count":4}"#)),
+        SendOutcome::Accepted
+    );
+    assert!(matches!(
+        send_outcome(3, reply(409, r#"{"error":"out of sync","action_count":2}"#)),
+        SendOutcome::Desync(_)
+    ));
+    // lost the write race, the count in the reply is not known
+    assert!(matches!(
+        send_outcome(3, reply(409, r#"{"error":"out of sync"}"#)),
+        SendOutcome::Retry(_)
+    ));
+    assert_eq!(
+        send_outcome(3, reply(422, r#"{"error":"not your turn"}"#)),
+        SendOutcome::Desync("not your turn".to_string())
+    );
+    assert!(matches!(
+        send_outcome(3, reply(500, "oops")),
+        SendOutcome::Retry(_)
+    ));
+    assert_eq!(
+        send_outcome(3, Err("network error".to_string())),
+        SendOutcome::Retry("network error".to_string())
+    );
+}
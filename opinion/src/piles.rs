@@ -0,0 +1,198 @@
+//! Pile views: a library or graveyard opened over the board as a scrollable
+//! grid of its cards.
+
+use macroquad::prelude as mcp;
+use opinion_rules::{Card, Tern};
+
+use crate::{CardLayout, Renderer, Shape, SizeRatio, fit_line};
+
+/// Cards shown side by side.
+const COLUMNS: usize = 6;
+
+/// The piles beside a player's rows, see `PlayerView::stack_rect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stack {
+    /// Face down, only its owner may look through it.
+    Library,
+    /// Face up, anyone may look through it.
+    Graveyard,
+}
+
+/// What is shown of a pile in a `PileBrowser`.
+pub enum PileContents {
+    /// Every card face up, in the order given.
+    Open(Vec<Card>),
+    /// Only how many cards there are.
+    Hidden(usize),
+}
+
+impl PileContents {
+    fn len(&self) -> usize {
+        match self {
+            PileContents::Open(cards) => cards.len(),
+            PileContents::Hidden(count) => *count,
+        }
+    }
+}
+
+/// One player's library or graveyard, opened after a click on it.
+pub struct PileBrowser {
+    side: Tern,
+    stack: Stack,
+    /// Rows scrolled past.
+    scroll: usize,
+}
+
+impl PileBrowser {
+    pub fn new(side: Tern, stack: Stack) -> Self {
+        Self {
+            side,
+            stack,
+            scroll: 0,
+        }
+    }
+
+    pub fn side(&self) -> Tern {
+        self.side
+    }
+
+    pub fn stack(&self) -> Stack {
+        self.stack
+    }
+
+    fn panel_rect() -> mcp::Rect {
+        SizeRatio::new(0.1, 0.08, 0.8, 0.84)
+    }
+
+    fn grid_rect() -> mcp::Rect {
+        SizeRatio::new(0.12, 0.16, 0.76, 0.7)
+    }
+
+    /// Size of one card, with the gap to the next one.
+    fn cell_size() -> (f32, f32) {
+        let w = PileBrowser::grid_rect().w / COLUMNS as f32;
+        (w, w * 1.4)
+    }
+
+    fn visible_rows() -> usize {
+        let (_, h) = PileBrowser::cell_size();
+        ((PileBrowser::grid_rect().h / h) as usize).max(1)
+    }
+
+    /// Each of `count` cards on screen with its index, scrolled rows left out.
+    fn card_rects(&self, count: usize) -> Vec<(usize, mcp::Rect)> {
+        let grid = PileBrowser::grid_rect();
+        let (w, h) = PileBrowser::cell_size();
+        let first = self.scroll * COLUMNS;
+        let last = (first + PileBrowser::visible_rows() * COLUMNS).min(count);
+
+        (first..last)
+            .map(|ind| {
+                let (row, col) = ((ind - first) / COLUMNS, (ind - first) % COLUMNS);
+                let rect = mcp::Rect::new(
+                    grid.x + col as f32 * w,
+                    grid.y + row as f32 * h,
+                    w * 0.92,
+                    h * 0.92,
+                );
+                (ind, rect)
+            })
+            .collect()
+    }
+
+    /// Scroll with the wheel. Returns false once the browser is closed, by
+    /// Esc, a right click or a click outside of it.
+    pub fn update(&mut self, count: usize, keyboard: bool) -> bool {
+        if keyboard && mcp::is_key_pressed(mcp::KeyCode::Escape) {
+            return false;
+        }
+        if mcp::is_mouse_button_pressed(mcp::MouseButton::Right) {
+            return false;
+        }
+        if mcp::is_mouse_button_pressed(mcp::MouseButton::Left)
+            && !PileBrowser::panel_rect().contains(mcp::mouse_position().into())
+        {
+            return false;
+        }
+
+        let rows = count.div_ceil(COLUMNS);
+        let max_scroll = rows.saturating_sub(PileBrowser::visible_rows());
+        let (_, wheel) = mcp::mouse_wheel();
+        if wheel < 0.0 {
+            self.scroll += 1;
+        } else if wheel > 0.0 {
+            self.scroll = self.scroll.saturating_sub(1);
+        }
+        // the pile may have shrunk since the last frame
+        self.scroll = self.scroll.min(max_scroll);
+        true
+    }
+
+    pub fn render(&self, contents: &PileContents, font: &mcp::Font) {
+        let screen = mcp::Rect::new(0.0, 0.0, mcp::screen_width(), mcp::screen_height());
+        Shape::draw_rect(screen, mcp::Color::new(0.0, 0.0, 0.0, 0.5));
+        let panel = PileBrowser::panel_rect();
+        Shape::draw_rect(panel, mcp::Color::from_rgba(24, 24, 32, 240));
+        mcp::draw_rectangle_lines(panel.x, panel.y, panel.w, panel.h, 2.0, mcp::GRAY);
+
+        let font_size = SizeRatio::get_y(0.04);
+        let params = |font_size: f32, color| mcp::TextParams {
+            font: Some(font),
+            font_size: font_size as u16,
+            color,
+            ..Default::default()
+        };
+
+        let count = contents.len();
+        let title = match (self.stack, contents) {
+            (Stack::Graveyard, _) => {
+                format!("{} graveyard - {} cards, top first", self.side, count)
+            }
+            (Stack::Library, PileContents::Open(_)) => {
+                format!("{} library - {} cards, order kept secret", self.side, count)
+            }
+            (Stack::Library, PileContents::Hidden(_)) => {
+                format!("{} library - {} cards, hidden", self.side, count)
+            }
+        };
+        let title = fit_line(&title, font, font_size, panel.w * 0.96, false);
+        let (x, y) = SizeRatio::get(0.12, 0.13);
+        mcp::draw_text_ex(&title, x, y, params(font_size, mcp::WHITE));
+
+        if count == 0 {
+            let (x, y) = SizeRatio::get(0.12, 0.22);
+            mcp::draw_text_ex("Empty", x, y, params(font_size, mcp::LIGHTGRAY));
+        }
+
+        let mouse = mcp::mouse_position().into();
+        let mut hovered = None;
+        for (ind, rect) in self.card_rects(count) {
+            match contents {
+                PileContents::Open(cards) => {
+                    Renderer::render_card(&cards[ind], rect, CardLayout::Minimal, font);
+                    if rect.contains(mouse) {
+                        hovered = Some(&cards[ind]);
+                    }
+                }
+                PileContents::Hidden(_) => Renderer::render_card_back(rect),
+            }
+        }
+
+        let rows = count.div_ceil(COLUMNS);
+        let mut help = "Esc, right click or click outside - Close".to_string();
+        if rows > PileBrowser::visible_rows() {
+            help = format!(
+                "Wheel - Scroll ({}/{})   {}",
+                self.scroll + 1,
+                rows + 1 - PileBrowser::visible_rows(),
+                help
+            );
+        }
+        let (x, y) = SizeRatio::get(0.12, 0.9);
+        mcp::draw_text_ex(&help, x, y, params(font_size * 0.6, mcp::LIGHTGRAY));
+
+        if let Some(card) = hovered {
+            Renderer::render_inspector(card, mouse, font);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! What survives a restart: JSON files in a config directory on native,
+//! `localStorage` entries in the browser. Every value is stored with
+//! `STORAGE_VERSION`, values written by another version are ignored.
+
+use macroquad::prelude as mcp;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::Difficulty;
+use opinion_rules::{Opening, Replay};
+
+/// Bumped whenever a stored type changes in a way older saves can't be read
+/// as, or a saved match would be dealt differently, see `RULES_VERSION`.
+pub const STORAGE_VERSION: u32 = 3;
+
+/// Decks built in the deck builder, see `deckbuilder::DeckList`.
+pub const DECKS_KEY: &str = "decks";
+pub const SETTINGS_KEY: &str = "settings";
+/// The logged in account, so players stay logged in across restarts.
+pub const SESSION_KEY: &str = "session";
+/// The local match left unfinished, see `SavedMatch`.
+pub const MATCH_KEY: &str = "match";
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    /// Looks the value up and returns its length in bytes, or -1 if there is none.
+    fn storage_get(key: *const u8, key_len: usize) -> i32;
+    /// Copies the value found by the last `storage_get` to `dst`.
+    fn storage_read(dst: *mut u8);
+    /// Returns 0 if the browser refused, when storage is full or disabled.
+    fn storage_set(key: *const u8, key_len: usize, value: *const u8, value_len: usize) -> i32;
+    fn storage_remove(key: *const u8, key_len: usize);
+}
+
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    version: u32,
+    data: T,
+}
+
+/// Choices made in the lobby and in matches that should stick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    /// Player2 of local matches, `None` for a second person at this machine.
+    pub opponent: Option<Difficulty>,
+    /// Filled into the login form.
+    pub username: String,
+    /// How local matches are dealt, settings saved before there was a
+    /// choice get the standard opening.
+    #[serde(default = "Opening::standard")]
+    pub opening: Opening,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            opponent: Some(Difficulty::Normal),
+            username: String::new(),
+            opening: Opening::standard(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        load(SETTINGS_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        save(SETTINGS_KEY, self);
+    }
+}
+
+/// A local match to pick up where it was left.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMatch {
+    pub replay: Replay,
+    /// Player2, as in `Settings::opponent`.
+    pub opponent: Option<Difficulty>,
+}
+
+/// Store `value` under `key`. Failures are logged, nothing stored is worth
+/// interrupting the game for.
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    let text = serde_json::to_string(&Versioned {
+        version: STORAGE_VERSION,
+        data: value,
+    })
+    .expect("stored values are always serializable");
+
+    if let Err(e) = write(key, &text) {
+        mcp::error!("could not store {}: {}", key, e);
+    }
+}
+
+/// The value stored under `key`, if there is one this version can read.
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let text = match read(key) {
+        Ok(text) => text?,
+        Err(e) => {
+            mcp::error!("could not read stored {}: {}", key, e);
+            return None;
+        }
+    };
+
+    // the version is looked at before the data, which may not parse any more
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    match serde_json::from_str::<Version>(&text) {
+        Ok(Version { version }) if version == STORAGE_VERSION => {}
+        Ok(Version { version }) => {
+            mcp::warn!("ignoring stored {} from version {}", key, version);
+            return None;
+        }
+        Err(e) => {
+            mcp::warn!("ignoring stored {}: {}", key, e);
+            return None;
+        }
+    }
+
+    match serde_json::from_str::<Versioned<T>>(&text) {
+        Ok(stored) => Some(stored.data),
+        Err(e) => {
+            mcp::warn!("ignoring stored {}: {}", key, e);
+            None
+        }
+    }
+}
+
+pub fn remove(key: &str) {
+    if let Err(e) = delete(key) {
+        mcp::error!("could not remove stored {}: {}", key, e);
+    }
+}
+
+/// `$XDG_CONFIG_HOME/opinion`, `%APPDATA%\opinion` or `~/.config/opinion`.
+#[cfg(not(target_arch = "wasm32"))]
+fn config_dir() -> Result<std::path::PathBuf, String> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+    let base = var("XDG_CONFIG_HOME")
+        .or_else(|| var("APPDATA"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| var("HOME").map(|home| std::path::Path::new(&home).join(".config")))
+        .ok_or("no config directory, HOME is not set")?;
+    Ok(base.join("opinion"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path_of(key: &str) -> Result<std::path::PathBuf, String> {
+    Ok(config_dir()?.join(format!("{}.json", key)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Result<Option<String>, String> {
+    let path = path_of(key)?;
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, text: &str) -> Result<(), String> {
+    let path = path_of(key)?;
+    let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(err)?;
+    }
+    // written aside first, so a crash mid-write keeps the old value
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, text).map_err(err)?;
+    std::fs::rename(&tmp, &path).map_err(err)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn delete(key: &str) -> Result<(), String> {
+    let path = path_of(key)?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("{}: {}", path.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Result<Option<String>, String> {
+    let key = format!("opinion.{}", key);
+    let len = unsafe { storage_get(key.as_ptr(), key.len()) };
+    if len < 0 {
+        return Ok(None);
+    }
+
+    let mut buf = vec![0u8; len as usize];
+    unsafe { storage_read(buf.as_mut_ptr()) };
+    String::from_utf8(buf).map(Some).map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, text: &str) -> Result<(), String> {
+    let key = format!("opinion.{}", key);
+    match unsafe { storage_set(key.as_ptr(), key.len(), text.as_ptr(), text.len()) } {
+        0 => Err("the browser refused to store it".to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn delete(key: &str) -> Result<(), String> {
+    let key = format!("opinion.{}", key);
+    unsafe { storage_remove(key.as_ptr(), key.len()) };
+    Ok(())
+}
@@ -0,0 +1,272 @@
+//! Converting card art to and from plain RGBA pixels, so it can be drawn in
+//! normal image tools.
+
+use crate::{CardImage, PaletteColor, Rgba};
+
+/// Characters given to the colours of an imported image, after `.` for
+/// transparent pixels. Also caps the number of colours.
+const PALETTE_KEYS: &str = "#abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Cells less covered than this come out transparent.
+const ALPHA_CUTOFF: u32 = 128;
+
+impl CardImage {
+    /// Downsample a `width * height` RGBA image (4 bytes per pixel, row by
+    /// row) to `rows * cols` cells and quantise it to at most `max_colors`
+    /// opaque colours. When every cell is already a colour of `palette`,
+    /// translucent ones included, that palette is kept, so exporting and
+    /// importing again changes nothing.
+    pub fn from_rgba(
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        rows: u16,
+        cols: u16,
+        max_colors: usize,
+        palette: &[PaletteColor],
+    ) -> Self {
+        let cells = downsample(width, height, rgba, rows, cols);
+
+        let transparent = palette.iter().position(|p| p.color.a == 0);
+        let exact = |cell: Rgba| match cell.a {
+            0 => transparent,
+            _ => palette.iter().position(|p| p.color == cell),
+        };
+        if transparent.is_some() && cells.iter().all(|&cell| exact(cell).is_some()) {
+            let img = cells
+                .iter()
+                .filter_map(|&cell| exact(cell))
+                .map(|i| i as u8);
+            return CardImage::from_indices(rows, cols, palette.to_vec(), img.collect());
+        }
+
+        // a fresh palette is opaque, half covered cells go one way or the other
+        let cells: Vec<Rgba> = cells
+            .into_iter()
+            .map(|cell| {
+                if (cell.a as u32) < ALPHA_CUTOFF {
+                    Rgba::TRANSPARENT
+                } else {
+                    Rgba::new(cell.r, cell.g, cell.b, 255)
+                }
+            })
+            .collect();
+
+        let mut colors: Vec<Rgba> = Vec::new();
+        for &cell in &cells {
+            if cell.a > 0 && !colors.contains(&cell) {
+                colors.push(cell);
+            }
+        }
+
+        let mut palette = vec![PaletteColor {
+            key: '.',
+            color: Rgba::TRANSPARENT,
+        }];
+        let limit = max_colors.clamp(1, PALETTE_KEYS.len());
+        palette.extend(
+            median_cut(colors, limit)
+                .into_iter()
+                .zip(PALETTE_KEYS.chars())
+                .map(|(color, key)| PaletteColor { key, color }),
+        );
+
+        let img = cells
+            .iter()
+            .map(|&cell| match cell.a {
+                0 => 0,
+                _ => nearest(&palette, cell) as u8,
+            })
+            .collect();
+
+        CardImage::from_indices(rows, cols, palette, img)
+    }
+
+    /// The image as `cols * rows` RGBA pixels, 4 bytes each, row by row.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.img.len() * 4);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let color = self.color(r, c);
+                out.extend([color.r, color.g, color.b, color.a]);
+            }
+        }
+        out
+    }
+}
+
+/// Average each cell's share of the source pixels, alpha included, so the
+/// translucent colours of an exported image come back unchanged.
+fn downsample(width: u32, height: u32, rgba: &[u8], rows: u16, cols: u16) -> Vec<Rgba> {
+    let (width, height) = (width as usize, height as usize);
+    let (rows, cols) = (rows as usize, cols as usize);
+    if width == 0 || height == 0 || rgba.len() < width * height * 4 {
+        return vec![Rgba::TRANSPARENT; rows * cols];
+    }
+
+    // a cell always covers at least one pixel, so small images scale up
+    let span = |i: usize, cells: usize, size: usize| {
+        let start = (i * size / cells).min(size - 1);
+        (start, ((i + 1) * size / cells).max(start + 1))
+    };
+
+    let mut cells = Vec::with_capacity(rows * cols);
+    for r in 0..rows {
+        let (y0, y1) = span(r, rows, height);
+        for c in 0..cols {
+            let (x0, x1) = span(c, cols, width);
+
+            let mut sum = [0u32; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let px = &rgba[(y * width + x) * 4..][..4];
+                    let a = px[3] as u32;
+                    // weight colour by alpha so transparent pixels don't darken edges
+                    sum[0] += px[0] as u32 * a;
+                    sum[1] += px[1] as u32 * a;
+                    sum[2] += px[2] as u32 * a;
+                    sum[3] += a;
+                }
+            }
+
+            let count = ((y1 - y0) * (x1 - x0)) as u32;
+            cells.push(if sum[3] == 0 {
+                Rgba::TRANSPARENT
+            } else {
+                let channel = |s: u32| ((s + sum[3] / 2) / sum[3]) as u8;
+                let alpha = ((sum[3] + count / 2) / count) as u8;
+                Rgba::new(channel(sum[0]), channel(sum[1]), channel(sum[2]), alpha)
+            });
+        }
+    }
+    cells
+}
+
+/// At most `limit` colours standing in for `colors`: the colour box with
+/// the widest channel is split at its median until there are enough boxes,
+/// then each box is replaced by its average.
+fn median_cut(colors: Vec<Rgba>, limit: usize) -> Vec<Rgba> {
+    if colors.len() <= limit {
+        return colors;
+    }
+
+    let channel = |c: &Rgba, ch: usize| [c.r, c.g, c.b][ch];
+    let widest = |bucket: &[Rgba]| {
+        (0..3)
+            .map(|ch| {
+                let values = bucket.iter().map(|c| channel(c, ch));
+                let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (range, ch)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut buckets = vec![colors];
+    while buckets.len() < limit {
+        let Some((ind, (_, ch))) = buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest(b)))
+            .max_by_key(|(_, (range, _))| *range)
+        else {
+            break;
+        };
+
+        let mut bucket = buckets.swap_remove(ind);
+        bucket.sort_by_key(|c| channel(c, ch));
+        let upper = bucket.split_off(bucket.len() / 2);
+        buckets.push(bucket);
+        buckets.push(upper);
+    }
+
+    buckets
+        .iter()
+        .map(|bucket| {
+            let n = bucket.len() as u32;
+            let avg = |ch| (bucket.iter().map(|c| channel(c, ch) as u32).sum::<u32>() / n) as u8;
+            Rgba::new(avg(0), avg(1), avg(2), 255)
+        })
+        .collect()
+}
+
+/// Index of the opaque palette colour closest to `color`.
+fn nearest(palette: &[PaletteColor], color: Rgba) -> usize {
+    let dist = |p: &PaletteColor| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(p.color.r, color.r) + d(p.color.g, color.g) + d(p.color.b, color.b)
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.color.a > 0)
+        .min_by_key(|(_, p)| dist(p))
+        .map_or(0, |(i, _)| i)
+}
+
+#[test]
+fn art_round_trip_test() {
+    let red = Rgba::new(255, 0, 0, 255);
+    let mut img = CardImage::new(2, 2);
+    img.palette.push(PaletteColor {
+        key: 'r',
+        color: red,
+    });
+    img.img = vec![0, 1, 2, 1];
+
+    let rgba = img.to_rgba();
+    assert_eq!(&rgba[8..12], &[255, 0, 0, 255]);
+    assert_eq!(
+        CardImage::from_rgba(2, 2, &rgba, 2, 2, 16, &img.palette),
+        img
+    );
+
+    // a fresh palette keeps transparent first
+    let imported = CardImage::from_rgba(2, 2, &rgba, 2, 2, 16, &[]);
+    assert_eq!(imported.palette[0].color, Rgba::TRANSPARENT);
+    assert_eq!(imported.color(1, 0), red);
+    assert_eq!(imported.color(0, 0), Rgba::TRANSPARENT);
+}
+
+#[test]
+fn art_translucent_round_trip_test() {
+    let key = |key, color| PaletteColor { key, color };
+    let palette = vec![
+        key('.', Rgba::TRANSPARENT),
+        key('s', Rgba::new(40, 60, 200, 0x60)),
+        key('g', Rgba::new(20, 180, 20, 0xc0)),
+    ];
+    let img = CardImage::from_indices(2, 2, palette, vec![1, 2, 0, 1]);
+
+    let rgba = img.to_rgba();
+    assert_eq!(&rgba[..4], &[40, 60, 200, 0x60]);
+    assert_eq!(
+        CardImage::from_rgba(2, 2, &rgba, 2, 2, 16, &img.palette),
+        img
+    );
+
+    // a fresh palette has no translucent colours, faint cells drop out
+    let imported = CardImage::from_rgba(2, 2, &rgba, 2, 2, 16, &[]);
+    assert_eq!(imported.color(0, 0), Rgba::TRANSPARENT);
+    assert_eq!(imported.color(0, 1), Rgba::new(20, 180, 20, 255));
+}
+
+#[test]
+fn art_downsample_test() {
+    // 4x2 image: left half black, right half two shades of blue
+    let mut rgba = Vec::new();
+    for _ in 0..2 {
+        rgba.extend([0, 0, 0, 255, 0, 0, 0, 255]);
+        rgba.extend([0, 0, 200, 255, 0, 0, 250, 255]);
+    }
+
+    let img = CardImage::from_rgba(4, 2, &rgba, 1, 2, 16, &[]);
+    assert_eq!(img.color(0, 0), Rgba::new(0, 0, 0, 255));
+    assert_eq!(img.color(0, 1), Rgba::new(0, 0, 225, 255));
+
+    let img = CardImage::from_rgba(4, 2, &rgba, 2, 4, 2, &[]);
+    assert_eq!(img.palette.len(), 3);
+    assert_eq!(img.get(0, 2), img.get(1, 3));
+}
@@ -0,0 +1,652 @@
+//! The card model and the card definitions file.
+
+use serde::Deserialize;
+use std::fmt;
+
+use crate::{MAX_LIFE, SimpleRng};
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum CardType {
+    /// Stays in the arena and deals its power every combat step.
+    Attack,
+    /// Stays in the arena and blocks attacks until it has taken its power in damage.
+    Defense,
+    /// Resolves its effects when played, then goes to the graveyard.
+    Magic,
+    /// Resolves its effects when played, then goes to the graveyard.
+    Support,
+}
+
+impl CardType {
+    /// Whether cards of this type stay in the arena after being played.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, CardType::Attack | CardType::Defense)
+    }
+
+    /// Rules text for the type, shown when a card is inspected.
+    pub fn explain(&self) -> &'static str {
+        match self {
+            CardType::Attack => "Stays in the arena and hits for its power every combat step.",
+            CardType::Defense => {
+                "Stays in the arena and blocks attacks until it has taken its power in damage."
+            }
+            CardType::Magic | CardType::Support => {
+                "Resolves its effects when played, then goes to the graveyard."
+            }
+        }
+    }
+}
+
+/// An 8-bit per channel colour, written `#rrggbb` or `#rrggbbaa` in card files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const TRANSPARENT: Rgba = Rgba::new(0, 0, 0, 0);
+    pub const WHITE: Rgba = Rgba::new(255, 255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn parse_hex(text: &str) -> Option<Self> {
+        let hex = text.strip_prefix('#')?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        let a = if hex.len() == 8 { channel(6)? } else { 255 };
+        Some(Self::new(channel(0)?, channel(2)?, channel(4)?, a))
+    }
+
+    /// `#rrggbb`, with the alpha channel only when it is not opaque.
+    pub fn to_hex(&self) -> String {
+        match self.a {
+            255 => format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b),
+            a => format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, a),
+        }
+    }
+}
+
+/// One colour of an image palette and the character that stands for it in
+/// the card definitions file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteColor {
+    pub key: char,
+    pub color: Rgba,
+}
+
+/// Palette of images that do not define their own: `.` for an empty
+/// pixel and `#` for a set one.
+pub const DEFAULT_PALETTE: [PaletteColor; 2] = [
+    PaletteColor {
+        key: '.',
+        color: Rgba::TRANSPARENT,
+    },
+    PaletteColor {
+        key: '#',
+        color: Rgba::WHITE,
+    },
+];
+
+/// Card art as palette indices, one per cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardImage {
+    pub rows: u16,
+    pub cols: u16,
+    /// At most 256 colours, the first one fills blank images.
+    pub palette: Vec<PaletteColor>,
+    /// `rows * cols` indices into `palette`, row by row.
+    pub img: Vec<u8>,
+}
+
+impl CardImage {
+    /// `img` holds `rows * cols` indices into `palette`, row by row. A
+    /// shorter list is padded with index 0, a longer one cut.
+    pub fn from_indices(
+        rows: u16,
+        cols: u16,
+        palette: Vec<PaletteColor>,
+        mut img: Vec<u8>,
+    ) -> Self {
+        img.resize(rows as usize * cols as usize, 0);
+        Self {
+            rows,
+            cols,
+            palette,
+            img,
+        }
+    }
+
+    /// Two-colour image in `DEFAULT_PALETTE` from on/off pixels, row by row.
+    pub fn from_bools(rows: u16, cols: u16, pixels: &[bool]) -> Self {
+        let img = pixels.iter().map(|&on| on as u8).collect();
+        Self::from_indices(rows, cols, DEFAULT_PALETTE.to_vec(), img)
+    }
+
+    /// Blank image of `rows * cols` cells in `DEFAULT_PALETTE`.
+    pub fn new(rows: u16, cols: u16) -> Self {
+        let img = vec![0; rows as usize * cols as usize];
+
+        Self::from_indices(rows, cols, DEFAULT_PALETTE.to_vec(), img)
+    }
+
+    fn index(&self, row: u16, col: u16) -> Option<usize> {
+        (row < self.rows && col < self.cols)
+            .then(|| row as usize * self.cols as usize + col as usize)
+    }
+
+    /// Palette index of a pixel, 0 outside the image.
+    pub fn get(&self, row: u16, col: u16) -> u8 {
+        self.index(row, col)
+            .and_then(|i| self.img.get(i).copied())
+            .unwrap_or(0)
+    }
+
+    /// Set a pixel to a palette index, ignoring positions outside the image.
+    pub fn set(&mut self, row: u16, col: u16, index: u8) {
+        if let Some(pixel) = self.index(row, col).and_then(|i| self.img.get_mut(i)) {
+            *pixel = index;
+        }
+    }
+
+    /// Colour of a pixel, transparent outside the image or the palette.
+    pub fn color(&self, row: u16, col: u16) -> Rgba {
+        self.index(row, col)
+            .and_then(|i| self.palette.get(*self.img.get(i)? as usize))
+            .map_or(Rgba::TRANSPARENT, |p| p.color)
+    }
+
+    /// Whether this image gets by without writing its palette out.
+    pub fn has_default_palette(&self) -> bool {
+        self.palette == DEFAULT_PALETTE
+    }
+
+    /// One string per row, the way the card definitions file stores images.
+    pub fn to_pixels(&self) -> Vec<String> {
+        let key = |i: u8| self.palette.get(i as usize).map_or('.', |p| p.key);
+        self.img
+            .chunks(self.cols.max(1) as usize)
+            .map(|row| row.iter().map(|&i| key(i)).collect())
+            .collect()
+    }
+}
+
+/// What a card does when it is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Effect {
+    /// The player draws this many cards.
+    Draw(u32),
+    /// The opponent loses this much life, shield first.
+    Damage(u32),
+    /// The player gains this much life, up to `MAX_LIFE`.
+    Heal(u32),
+    /// The player gains this much shield.
+    Shield(u32),
+    /// The opponent discards this many cards from the end of their hand.
+    Discard(u32),
+}
+
+impl Effect {
+    /// Reminder of the rule behind the effect's keyword.
+    pub fn explain(&self) -> String {
+        match self {
+            Effect::Draw(_) => {
+                "Draw: cards come off the library. Drawing from an empty library loses the match."
+                    .to_string()
+            }
+            Effect::Damage(_) => {
+                "Damage: shield soaks it up first, the rest comes off life.".to_string()
+            }
+            Effect::Heal(_) => format!("Heal: life never goes above {}.", MAX_LIFE),
+            Effect::Shield(_) => {
+                "Shield: takes damage before life and stays until used up.".to_string()
+            }
+            Effect::Discard(_) => {
+                "Discard: cards leave the end of the hand for the graveyard.".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |n: u32| if n == 1 { "" } else { "s" };
+        match *self {
+            Effect::Draw(n) => write!(f, "Draw {} card{}.", n, plural(n)),
+            Effect::Damage(n) => write!(f, "Deal {} damage.", n),
+            Effect::Heal(n) => write!(f, "Heal {}.", n),
+            Effect::Shield(n) => write!(f, "Gain {} shield.", n),
+            Effect::Discard(n) => write!(f, "Opponent discards {} card{}.", n, plural(n)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Card {
+    pub img: CardImage,
+    pub name: String,
+    /// Generated from `effects`, never set by hand.
+    pub desc: String,
+    pub power: u32,
+    /// Mana spent from the player's pool to play the card.
+    pub cost: u32,
+    pub card_type: CardType,
+    pub effects: Vec<Effect>,
+    /// Combat damage taken so far, a `Defense` card is destroyed once it reaches `power`.
+    pub damage: u32,
+}
+
+impl Card {
+    pub fn new(
+        img: CardImage,
+        name: &str,
+        effects: Vec<Effect>,
+        power: u32,
+        cost: u32,
+        card_type: CardType,
+    ) -> Self {
+        Self {
+            img,
+            power,
+            cost,
+            card_type,
+            name: name.to_string(),
+            desc: Card::describe(&effects),
+            effects,
+            damage: 0,
+        }
+    }
+
+    /// Append free flavour text after the generated effect text.
+    pub fn with_flavor(mut self, flavor: &str) -> Self {
+        if !flavor.is_empty() {
+            self.desc = format!("{} {}", self.desc, flavor).trim().to_string();
+        }
+        self
+    }
+
+    fn describe(effects: &[Effect]) -> String {
+        effects
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Card: {} Type: {:?} Power: {} Cost: {}\nDescription: {}\n",
+            self.name, self.card_type, self.power, self.cost, self.desc
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CardCollection {
+    pub col: Vec<Card>,
+}
+
+/// Why the card definitions file could not be turned into a `CardCollection`.
+#[derive(Debug)]
+pub enum CardLoadError {
+    /// The file could not be read.
+    Io { path: String, msg: String },
+    /// The file is not valid JSON, or not a list.
+    Syntax { path: String, msg: String },
+    /// One entry of the list is not a valid card.
+    Entry {
+        path: String,
+        index: usize,
+        name: Option<String>,
+        msg: String,
+    },
+}
+
+impl fmt::Display for CardLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CardLoadError::Io { path, msg } => write!(f, "{}: could not read file: {}", path, msg),
+            CardLoadError::Syntax { path, msg } => write!(f, "{}: {}", path, msg),
+            CardLoadError::Entry {
+                path,
+                index,
+                name: Some(name),
+                msg,
+            } => write!(f, "{}: card #{} ({:?}): {}", path, index, name, msg),
+            CardLoadError::Entry {
+                path,
+                index,
+                name: None,
+                msg,
+            } => write!(f, "{}: card #{}: {}", path, index, msg),
+        }
+    }
+}
+
+/// One entry of the card definitions file, as written by designers.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CardDef {
+    name: String,
+    /// Flavour text shown after the generated effect text.
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    power: u32,
+    #[serde(default)]
+    cost: u32,
+    card_type: CardType,
+    #[serde(default)]
+    effects: Vec<Effect>,
+    image: CardImageDef,
+}
+
+/// Card art as one string per row, one character per pixel. Without a
+/// palette `.` is an empty pixel and `#` a set one, see `DEFAULT_PALETTE`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CardImageDef {
+    rows: u16,
+    cols: u16,
+    pixels: Vec<String>,
+    /// Character used in `pixels` to the colour it stands for, in palette order.
+    #[serde(default)]
+    palette: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl CardImageDef {
+    fn palette(&self) -> Result<Vec<PaletteColor>, String> {
+        let Some(entries) = &self.palette else {
+            return Ok(DEFAULT_PALETTE.to_vec());
+        };
+        if entries.is_empty() || entries.len() > 256 {
+            return Err("palette must have 1 to 256 colours".to_string());
+        }
+
+        entries
+            .iter()
+            .map(|(key, color)| {
+                let mut chars = key.chars();
+                let (Some(key), None) = (chars.next(), chars.next()) else {
+                    return Err(format!("palette key {:?} is not a single character", key));
+                };
+                let color = color.as_str().and_then(Rgba::parse_hex).ok_or_else(|| {
+                    format!("palette colour {} is not #rrggbb or #rrggbbaa", color)
+                })?;
+                Ok(PaletteColor { key, color })
+            })
+            .collect()
+    }
+
+    fn to_image(&self) -> Result<CardImage, String> {
+        if self.pixels.len() != self.rows as usize {
+            return Err(format!(
+                "image has {} pixel rows, expected {}",
+                self.pixels.len(),
+                self.rows
+            ));
+        }
+        let palette = self.palette()?;
+
+        let mut img = Vec::with_capacity(self.rows as usize * self.cols as usize);
+        for (r, row) in self.pixels.iter().enumerate() {
+            if row.chars().count() != self.cols as usize {
+                return Err(format!(
+                    "image row {} has {} pixels, expected {}",
+                    r,
+                    row.chars().count(),
+                    self.cols
+                ));
+            }
+
+            for (c, ch) in row.chars().enumerate() {
+                match palette.iter().position(|p| p.key == ch) {
+                    Some(index) => img.push(index as u8),
+                    None => {
+                        return Err(format!(
+                            "image row {} column {}: {:?} is not in the palette",
+                            r, c, ch
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(CardImage::from_indices(self.rows, self.cols, palette, img))
+    }
+}
+
+impl CardCollection {
+    /// Parse the contents of a card definitions file. `path` is only used in errors.
+    pub fn parse(path: &str, text: &str) -> Result<Self, CardLoadError> {
+        // parse in two steps so an error can name the entry it came from
+        let entries: Vec<serde_json::Value> =
+            serde_json::from_str(text).map_err(|e| CardLoadError::Syntax {
+                path: path.to_string(),
+                msg: e.to_string(),
+            })?;
+
+        let mut col = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.into_iter().enumerate() {
+            let name = entry
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(str::to_string);
+            let entry_err = |msg: String| CardLoadError::Entry {
+                path: path.to_string(),
+                index,
+                name: name.clone(),
+                msg,
+            };
+
+            let def: CardDef =
+                serde_json::from_value(entry).map_err(|e| entry_err(e.to_string()))?;
+            let img = def.image.to_image().map_err(entry_err)?;
+
+            col.push(
+                Card::new(
+                    img,
+                    &def.name,
+                    def.effects,
+                    def.power,
+                    def.cost,
+                    def.card_type,
+                )
+                .with_flavor(&def.desc),
+            );
+        }
+
+        if col.is_empty() {
+            return Err(CardLoadError::Syntax {
+                path: path.to_string(),
+                msg: "no cards defined".to_string(),
+            });
+        }
+
+        Ok(Self { col })
+    }
+
+    /// Write the images of this collection into `text`, the card definitions
+    /// it was parsed from, keeping everything else in the file as it is.
+    pub fn write_images(&self, path: &str, text: &str) -> Result<String, CardLoadError> {
+        let syntax_err = |msg: String| CardLoadError::Syntax {
+            path: path.to_string(),
+            msg,
+        };
+
+        let mut entries: Vec<serde_json::Value> =
+            serde_json::from_str(text).map_err(|e| syntax_err(e.to_string()))?;
+        if entries.len() != self.col.len() {
+            return Err(syntax_err(format!(
+                "file has {} cards, expected {}",
+                entries.len(),
+                self.col.len()
+            )));
+        }
+
+        for (entry, card) in entries.iter_mut().zip(&self.col) {
+            let img = &card.img;
+            let mut image = serde_json::json!({
+                "rows": img.rows,
+                "cols": img.cols,
+                "pixels": img.to_pixels(),
+            });
+            if !img.has_default_palette() {
+                let palette: serde_json::Map<_, _> = img
+                    .palette
+                    .iter()
+                    .map(|p| (p.key.to_string(), p.color.to_hex().into()))
+                    .collect();
+                image["palette"] = palette.into();
+            }
+            entry["image"] = image;
+        }
+
+        // four spaces, like the hand written file
+        let mut out = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+        serde::Serialize::serialize(&entries, &mut ser).expect("JSON values always serialize");
+        out.push(b'\n');
+        Ok(String::from_utf8(out).expect("serde_json writes UTF-8"))
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Card> {
+        self.col.iter().find(|c| c.name == name)
+    }
+
+    /// `count` cards drawn from the whole collection, each card can come up
+    /// any number of times. Nothing for an empty collection.
+    pub fn get_random(&self, rng: &mut SimpleRng, count: usize) -> Vec<Card> {
+        rng.choose_multiple_with_replacement(&self.col, count)
+    }
+}
+
+#[test]
+fn card_parse_test() {
+    let col = CardCollection::parse(
+        "cards.json",
+        r##"[{
+            "name": "Spark",
+            "desc": "Hot.",
+            "cost": 1,
+            "card_type": "Magic",
+            "effects": [{ "Damage": 2 }],
+            "image": { "rows": 1, "cols": 2, "pixels": ["#."] }
+        }]"##,
+    )
+    .unwrap();
+    assert_eq!(col.col[0].desc, "Deal 2 damage. Hot.");
+    assert_eq!(col.col[0].img.img, vec![1, 0]);
+
+    let err = CardCollection::parse(
+        "cards.json",
+        r##"[{
+            "name": "Broken",
+            "card_type": "Magic",
+            "image": { "rows": 1, "cols": 2, "pixels": ["#x"] }
+        }]"##,
+    )
+    .unwrap_err();
+    assert!(matches!(err, CardLoadError::Entry { index: 0, .. }));
+    assert!(err.to_string().contains("\"Broken\""));
+
+    let col = CardCollection::parse(
+        "cards.json",
+        r##"[{
+            "name": "Ember",
+            "card_type": "Magic",
+            "image": {
+                "rows": 1, "cols": 3, "pixels": ["rgr"],
+                "palette": { "r": "#ff0000", "g": "#00ff0080" }
+            }
+        }]"##,
+    )
+    .unwrap();
+    let img = &col.col[0].img;
+    assert_eq!(img.img, vec![0, 1, 0]);
+    assert_eq!(img.color(0, 1), Rgba::new(0, 255, 0, 128));
+    assert_eq!(img.color(0, 9), Rgba::TRANSPARENT);
+}
+
+#[test]
+fn card_image_test() {
+    let mut img = CardImage::new(2, 3);
+    assert_eq!(img.img.len(), 6);
+
+    img.set(1, 2, 1);
+    img.set(5, 5, 1);
+    assert_eq!(img.get(1, 2), 1);
+    assert_eq!(img.get(5, 5), 0);
+    assert_eq!(img.to_pixels(), vec!["...", "..#"]);
+    assert_eq!(
+        CardImage::from_bools(2, 3, &[false, false, false, false, false, true]),
+        img
+    );
+
+    let text = r##"[{
+        "name": "Spark",
+        "card_type": "Magic",
+        "image": { "rows": 2, "cols": 3, "pixels": ["...", "..."] }
+    }]"##;
+    let mut col = CardCollection::parse("cards.json", text).unwrap();
+    col.col[0].img = img;
+    let written = col.write_images("cards.json", text).unwrap();
+    let reread = CardCollection::parse("cards.json", &written).unwrap();
+    assert_eq!(reread.col[0].img.img, col.col[0].img.img);
+    assert!(written.find("\"name\"") < written.find("\"image\""));
+    assert!(!written.contains("palette"));
+
+    col.col[0].img.palette[1].color = Rgba::new(255, 0, 0, 255);
+    let written = col.write_images("cards.json", text).unwrap();
+    let reread = CardCollection::parse("cards.json", &written).unwrap();
+    assert_eq!(reread.col[0].img, col.col[0].img);
+    assert_eq!(Rgba::parse_hex("#ff000080").unwrap().to_hex(), "#ff000080");
+
+    // indices are public, a short list reads as blank instead of panicking
+    let mut short = CardImage::from_indices(2, 2, DEFAULT_PALETTE.to_vec(), vec![1]);
+    assert_eq!(short.img, vec![1, 0, 0, 0]);
+    short.img.truncate(1);
+    short.set(1, 1, 1);
+    assert_eq!(short.get(1, 1), 0);
+    assert_eq!(short.color(1, 1), Rgba::TRANSPARENT);
+}
+
+/// Two cheap cards, a `Magic` "Bolt" and an `Attack` "Soldier", for the
+/// tests of every module.
+#[cfg(test)]
+pub(crate) fn test_collection() -> CardCollection {
+    CardCollection::parse(
+        "cards.json",
+        r##"[
+            { "name": "Bolt", "cost": 1, "card_type": "Magic", "effects": [{ "Damage": 3 }],
+              "image": { "rows": 1, "cols": 1, "pixels": ["#"] } },
+            { "name": "Soldier", "power": 2, "cost": 1, "card_type": "Attack",
+              "image": { "rows": 1, "cols": 1, "pixels": ["#"] } }
+        ]"##,
+    )
+    .unwrap()
+}
+
+#[test]
+fn card_get_random_test() {
+    let col = test_collection();
+    let mut rng = SimpleRng::from_seed(5);
+
+    // the last card of the collection comes up too
+    let cards = col.get_random(&mut rng, 100);
+    assert_eq!(cards.len(), 100);
+    assert!(cards.iter().any(|c| c.name == "Bolt"));
+    assert!(cards.iter().any(|c| c.name == "Soldier"));
+
+    assert!(CardCollection::default().get_random(&mut rng, 3).is_empty());
+}
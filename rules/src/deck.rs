@@ -0,0 +1,149 @@
+//! Decks players build from the card collection instead of a random library.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{Card, CardCollection, LIBRARY_SIZE};
+
+/// Cards in a legal deck, one library's worth.
+pub const DECK_SIZE: usize = LIBRARY_SIZE;
+
+/// Most copies of one card a deck may hold.
+pub const MAX_COPIES: usize = 3;
+
+/// Longest deck name, in characters.
+pub const MAX_DECK_NAME_LEN: usize = 24;
+
+/// A named list of cards from a `CardCollection`, by card name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deck {
+    pub name: String,
+    /// One entry per copy, in the order they were added.
+    pub cards: Vec<String>,
+}
+
+/// Why a deck can't be played, or a card can't be added to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeckError {
+    /// A legal deck holds exactly `DECK_SIZE` cards, this one would hold this many.
+    WrongSize(usize),
+    /// More than `MAX_COPIES` of the named card.
+    TooManyCopies(String),
+    /// The named card is not in the collection.
+    UnknownCard(String),
+    EmptyName,
+}
+
+impl fmt::Display for DeckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeckError::WrongSize(size) => {
+                write!(f, "a deck holds {} cards, not {}", DECK_SIZE, size)
+            }
+            DeckError::TooManyCopies(name) => {
+                write!(f, "at most {} copies of {}", MAX_COPIES, name)
+            }
+            DeckError::UnknownCard(name) => write!(f, "there is no card called {}", name),
+            DeckError::EmptyName => write!(f, "the deck needs a name"),
+        }
+    }
+}
+
+impl Deck {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            cards: Vec::new(),
+        }
+    }
+
+    /// Copies of the card called `name`.
+    pub fn count(&self, name: &str) -> usize {
+        self.cards.iter().filter(|c| *c == name).count()
+    }
+
+    /// Add a copy of the card called `name`, unless that breaks the rules.
+    pub fn add(&mut self, col: &CardCollection, name: &str) -> Result<(), DeckError> {
+        if col.by_name(name).is_none() {
+            return Err(DeckError::UnknownCard(name.to_string()));
+        }
+        if self.cards.len() >= DECK_SIZE {
+            return Err(DeckError::WrongSize(self.cards.len() + 1));
+        }
+        if self.count(name) >= MAX_COPIES {
+            return Err(DeckError::TooManyCopies(name.to_string()));
+        }
+        self.cards.push(name.to_string());
+        Ok(())
+    }
+
+    /// Take out the last added copy of `name`, returns false if there was none.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.cards.iter().rposition(|c| c == name) {
+            Some(ind) => {
+                self.cards.remove(ind);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the deck can be played with cards from `col`.
+    pub fn check(&self, col: &CardCollection) -> Result<(), DeckError> {
+        if self.name.trim().is_empty() {
+            return Err(DeckError::EmptyName);
+        }
+        if let Some(unknown) = self.cards.iter().find(|c| col.by_name(c).is_none()) {
+            return Err(DeckError::UnknownCard(unknown.clone()));
+        }
+        if let Some(copied) = self.cards.iter().find(|c| self.count(c) > MAX_COPIES) {
+            return Err(DeckError::TooManyCopies(copied.clone()));
+        }
+        if self.cards.len() != DECK_SIZE {
+            return Err(DeckError::WrongSize(self.cards.len()));
+        }
+        Ok(())
+    }
+
+    /// The cards of the deck, in deck order. Names missing from `col` are
+    /// left out, `check` the deck first.
+    pub fn cards(&self, col: &CardCollection) -> Vec<Card> {
+        self.cards
+            .iter()
+            .filter_map(|name| col.by_name(name).cloned())
+            .collect()
+    }
+}
+
+#[test]
+fn deck_rules_test() {
+    let col = crate::card::test_collection();
+
+    let mut deck = Deck::new("Burn");
+    assert_eq!(
+        deck.add(&col, "Dragon"),
+        Err(DeckError::UnknownCard("Dragon".to_string()))
+    );
+    for _ in 0..MAX_COPIES {
+        deck.add(&col, "Bolt").unwrap();
+    }
+    assert_eq!(
+        deck.add(&col, "Bolt"),
+        Err(DeckError::TooManyCopies("Bolt".to_string()))
+    );
+    assert_eq!(deck.check(&col), Err(DeckError::WrongSize(MAX_COPIES)));
+
+    assert!(deck.remove("Bolt"));
+    assert!(!deck.remove("Soldier"));
+    assert_eq!(deck.count("Bolt"), MAX_COPIES - 1);
+
+    deck.cards = vec!["Soldier".to_string(); DECK_SIZE];
+    assert_eq!(
+        deck.check(&col),
+        Err(DeckError::TooManyCopies("Soldier".to_string()))
+    );
+    deck.cards = (0..DECK_SIZE).map(|i| format!("Copy {}", i % 2)).collect();
+    assert!(matches!(deck.check(&col), Err(DeckError::UnknownCard(_))));
+    deck.name = " ".to_string();
+    assert_eq!(deck.check(&col), Err(DeckError::EmptyName));
+}
@@ -0,0 +1,870 @@
+//! Turn structure of a match and the actions players take in it.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{
+    Card, CardCollection, CardType, Compensation, Deck, Effect, MAX_HAND_SIZE, Mulligan, Opening,
+    Player, SimpleRng,
+};
+
+#[cfg(test)]
+use crate::card::test_collection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tern {
+    Player1,
+    Player2,
+}
+
+impl Tern {
+    pub fn other(self) -> Self {
+        match self {
+            Tern::Player1 => Tern::Player2,
+            Tern::Player2 => Tern::Player1,
+        }
+    }
+}
+
+impl fmt::Display for Tern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tern::Player1 => write!(f, "Player 1"),
+            Tern::Player2 => write!(f, "Player 2"),
+        }
+    }
+}
+
+/// Steps of a single turn, in the order they are played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    /// Before the first turn each player, player1 first, decides on their
+    /// opening hand. Only when the `Opening` allows a mulligan.
+    Mulligan,
+    /// The active player draws a card, then the game moves on by itself.
+    Draw,
+    /// The active player plays cards from hand until they end the phase.
+    Main,
+    /// Cards in the arena fight until the active player ends the phase.
+    Combat,
+    /// Hand size is enforced and the turn passes to the other player.
+    End,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A single decision made by a player. Everything else in a match follows
+/// from the seed, so the seed plus these actions replays a match exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameAction {
+    /// Play the card at `hand_index` from the player's hand.
+    PlayCard { player: Tern, hand_index: usize },
+    /// Leave the current main or combat phase.
+    EndPhase { player: Tern },
+    /// Keep or change the opening hand, once per player.
+    Mulligan { player: Tern, choice: Mulligan },
+}
+
+impl GameAction {
+    pub fn player(&self) -> Tern {
+        match *self {
+            GameAction::PlayCard { player, .. }
+            | GameAction::EndPhase { player }
+            | GameAction::Mulligan { player, .. } => player,
+        }
+    }
+}
+
+/// Something that happened to the cards while an action was applied, for
+/// the client to animate. Indexes are into the piles as they were at that
+/// moment, so the events have to be followed in order.
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// The library was shuffled.
+    Shuffled { player: Tern },
+    /// `card` went from the top of the library to the end of the hand.
+    Drew { player: Tern, card: Card },
+    /// `card` left the hand for the arena, or for the graveyard if it is not
+    /// a permanent.
+    Played {
+        player: Tern,
+        hand_index: usize,
+        card: Card,
+    },
+    /// The arena card at `attacker` struck the opponent's arena card at
+    /// `blocker`, or the opponent directly.
+    Attacked {
+        player: Tern,
+        attacker: usize,
+        blocker: Option<usize>,
+    },
+    /// `card` went from the arena to the graveyard.
+    Destroyed {
+        player: Tern,
+        arena_index: usize,
+        card: Card,
+    },
+    /// `card` went from the hand to the graveyard.
+    Discarded {
+        player: Tern,
+        hand_index: usize,
+        card: Card,
+    },
+    /// `card` went from the hand to the bottom of the library.
+    Returned {
+        player: Tern,
+        hand_index: usize,
+        card: Card,
+    },
+}
+
+/// Version of how a seed deals, stored with every match. Bumped whenever the
+/// same seed and actions would play out differently, as matches of another
+/// version can't be rebuilt:
+///
+/// 1. Every match recorded before the version was stored.
+/// 2. `SimpleRng` ranges without modulo bias, random libraries drawn from
+///    the whole collection, opening hands dealt top card first like draws.
+///
+/// Older replay files are refused by `Replay::from_json`, older online
+/// matches by the API, and unfinished local matches are dropped with the
+/// client's storage version.
+pub const RULES_VERSION: u32 = 2;
+
+/// Everything needed to reproduce a match: its seed, the decks, the
+/// opening rules and every accepted action in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// `RULES_VERSION` the match was dealt with.
+    #[serde(default = "Replay::first_version")]
+    pub rules_version: u32,
+    pub seed: u64,
+    pub actions: Vec<GameAction>,
+    /// Deck of each player, player1 first. `None` played a random library.
+    #[serde(default)]
+    pub decks: [Option<Deck>; 2],
+    #[serde(default)]
+    pub opening: Opening,
+}
+
+impl Replay {
+    pub fn new(seed: u64, decks: [Option<Deck>; 2], opening: Opening) -> Self {
+        Self {
+            rules_version: RULES_VERSION,
+            seed,
+            actions: Vec::new(),
+            decks,
+            opening,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("replay is always serializable")
+    }
+
+    /// Parse a replay, refusing one dealt by other rules than these.
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> {
+        let replay: Replay = serde_json::from_str(text)?;
+        if replay.rules_version != RULES_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "recorded with rules version {}, this is version {}",
+                replay.rules_version, RULES_VERSION
+            )));
+        }
+        Ok(replay)
+    }
+
+    fn first_version() -> u32 {
+        1
+    }
+}
+
+/// Why `GameState::apply` turned an action down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionError {
+    GameOver,
+    NotYourTurn,
+    /// Cards are played in the main phase, phases are ended in main or combat,
+    /// mulligans are taken before the first turn.
+    WrongPhase,
+    NoSuchCard,
+    CannotAfford,
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::GameOver => write!(f, "the match is over"),
+            ActionError::NotYourTurn => write!(f, "not your turn"),
+            ActionError::WrongPhase => write!(f, "not allowed in this phase"),
+            ActionError::NoSuchCard => write!(f, "no card at this hand index"),
+            ActionError::CannotAfford => write!(f, "not enough mana"),
+        }
+    }
+}
+
+/// The rules side of a match, without anything on screen. Built from a seed
+/// and driven only by `apply`, so every copy given the same actions agrees.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    pub player1: Player,
+    pub player2: Player,
+    pub turn: Tern,
+    pub phase: Phase,
+    pub turn_count: u32,
+
+    pub is_running: bool,
+    /// Set once the match is over, `None` for a draw.
+    pub winner: Option<Tern>,
+
+    /// The only source of randomness in a match.
+    pub rng: SimpleRng,
+    /// How the hands were dealt, and what player2 gets for going second.
+    pub opening: Opening,
+
+    /// What the last `apply`, or dealing the match, did to the cards.
+    pub events: Vec<GameEvent>,
+}
+
+impl GameState {
+    /// Match where both players get a random library.
+    pub fn new(card_collection: &CardCollection, seed: u64) -> Self {
+        GameState::with_decks(card_collection, seed, &[None, None])
+    }
+
+    /// Match with the given deck for each player, player1 first. Decks
+    /// should pass `Deck::check`, `None` deals a random library.
+    pub fn with_decks(
+        card_collection: &CardCollection,
+        seed: u64,
+        decks: &[Option<Deck>; 2],
+    ) -> Self {
+        GameState::with_opening(card_collection, seed, decks, Opening::default())
+    }
+
+    /// Match with the given decks, dealt by the `opening` rules.
+    pub fn with_opening(
+        card_collection: &CardCollection,
+        seed: u64,
+        decks: &[Option<Deck>; 2],
+        opening: Opening,
+    ) -> Self {
+        let mut rng = SimpleRng::from_seed(seed);
+
+        let mut state = Self {
+            player1: Player::new(card_collection, decks[0].as_ref(), &mut rng),
+            player2: Player::new(card_collection, decks[1].as_ref(), &mut rng),
+            turn: Tern::Player1,
+            phase: Phase::Draw,
+            turn_count: 1,
+
+            is_running: true,
+            winner: None,
+
+            rng,
+            opening,
+            events: Vec::new(),
+        };
+
+        for side in [Tern::Player1, Tern::Player2] {
+            state.events.push(GameEvent::Shuffled { player: side });
+            let bonus =
+                (side == Tern::Player2 && opening.compensation == Compensation::ExtraCard) as usize;
+            state.deal(side, (opening.hand_size + bonus).min(MAX_HAND_SIZE));
+        }
+        if opening.mulligan {
+            state.phase = Phase::Mulligan;
+        }
+        state.run_automatic_phases();
+        state
+    }
+
+    /// Rebuild a match from its replay, stopping at the first rejected action.
+    pub fn from_replay(
+        card_collection: &CardCollection,
+        replay: &Replay,
+    ) -> Result<Self, ActionError> {
+        let mut state =
+            GameState::with_opening(card_collection, replay.seed, &replay.decks, replay.opening);
+        for &action in &replay.actions {
+            state.apply(action)?;
+        }
+        Ok(state)
+    }
+
+    pub fn player(&self, side: Tern) -> &Player {
+        match side {
+            Tern::Player1 => &self.player1,
+            Tern::Player2 => &self.player2,
+        }
+    }
+
+    pub fn active_player_mut(&mut self) -> &mut Player {
+        self.player_mut(self.turn)
+    }
+
+    fn player_mut(&mut self, side: Tern) -> &mut Player {
+        match side {
+            Tern::Player1 => &mut self.player1,
+            Tern::Player2 => &mut self.player2,
+        }
+    }
+
+    /// The active player and their opponent, in that order.
+    fn players_mut(&mut self) -> (&mut Player, &mut Player) {
+        match self.turn {
+            Tern::Player1 => (&mut self.player1, &mut self.player2),
+            Tern::Player2 => (&mut self.player2, &mut self.player1),
+        }
+    }
+
+    /// Apply an action for the active player. Changes nothing if the action
+    /// is not legal right now.
+    pub fn apply(&mut self, action: GameAction) -> Result<(), ActionError> {
+        self.check(action)?;
+        self.events.clear();
+
+        match action {
+            GameAction::PlayCard { hand_index, .. } => {
+                let side = self.turn;
+                let player = self.active_player_mut();
+                let card = player.hand[hand_index].clone();
+                let effects = player
+                    .play_card(hand_index)
+                    .expect("card was checked to be playable");
+                self.events.push(GameEvent::Played {
+                    player: side,
+                    hand_index,
+                    card,
+                });
+                self.resolve_effects(&effects);
+            }
+            GameAction::EndPhase { .. } => match self.phase {
+                Phase::Main => {
+                    self.resolve_combat();
+                    self.phase = Phase::Combat;
+                }
+                Phase::Combat => self.phase = Phase::End,
+                Phase::Mulligan | Phase::Draw | Phase::End => unreachable!("phase was checked"),
+            },
+            GameAction::Mulligan { choice, .. } => self.mulligan(choice),
+        }
+
+        self.run_automatic_phases();
+        self.check_game_over();
+        Ok(())
+    }
+
+    /// Why `action` can't be applied right now, if it can't.
+    fn check(&self, action: GameAction) -> Result<(), ActionError> {
+        if !self.is_running {
+            return Err(ActionError::GameOver);
+        }
+        if action.player() != self.turn {
+            return Err(ActionError::NotYourTurn);
+        }
+
+        match action {
+            GameAction::PlayCard { hand_index, .. } => {
+                if self.phase != Phase::Main {
+                    return Err(ActionError::WrongPhase);
+                }
+                let player = self.player(self.turn);
+                match player.hand.get(hand_index) {
+                    None => Err(ActionError::NoSuchCard),
+                    Some(card) if !player.can_afford(card) => Err(ActionError::CannotAfford),
+                    Some(_) => Ok(()),
+                }
+            }
+            GameAction::EndPhase { .. } => match self.phase {
+                Phase::Main | Phase::Combat => Ok(()),
+                Phase::Mulligan | Phase::Draw | Phase::End => Err(ActionError::WrongPhase),
+            },
+            GameAction::Mulligan { choice, .. } => {
+                if self.phase != Phase::Mulligan {
+                    return Err(ActionError::WrongPhase);
+                }
+                let hand = self.player(self.turn).hand.len() as u32;
+                match choice {
+                    Mulligan::Bottom(cards) if cards.checked_shr(hand).unwrap_or(0) != 0 => {
+                        Err(ActionError::NoSuchCard)
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Step through the phases that need no decision, stopping at the next
+    /// phase that waits for the active player.
+    fn run_automatic_phases(&mut self) {
+        while self.is_running {
+            match self.phase {
+                Phase::Draw => {
+                    let first_turn = self.turn_count == 1;
+                    self.active_player_mut().start_turn();
+                    // the starting player skips their very first draw
+                    if !first_turn {
+                        self.draw(self.turn, 1);
+                    }
+                    if self.turn_count == 2 && self.opening.compensation == Compensation::ExtraMana
+                    {
+                        self.active_player_mut().mana += 1;
+                    }
+                    self.phase = Phase::Main;
+                }
+                Phase::End => self.end_turn(),
+                Phase::Mulligan | Phase::Main | Phase::Combat => break,
+            }
+            self.check_game_over();
+        }
+    }
+
+    /// Stop the match once either player is defeated.
+    fn check_game_over(&mut self) {
+        let (p1_lost, p2_lost) = (self.player1.is_defeated(), self.player2.is_defeated());
+        if !(p1_lost || p2_lost) {
+            return;
+        }
+
+        self.is_running = false;
+        self.winner = match (p1_lost, p2_lost) {
+            (false, true) => Some(Tern::Player1),
+            (true, false) => Some(Tern::Player2),
+            _ => None,
+        };
+    }
+
+    /// Every Attack card of the active player strikes once. The first Defense
+    /// card in the opponent's arena blocks the hit, otherwise it lands on the
+    /// opponent. Defense cards that took their power in damage are destroyed.
+    fn resolve_combat(&mut self) {
+        let side = self.turn;
+        let mut events = Vec::new();
+        let (player, opponent) = self.players_mut();
+        let attackers = player
+            .arena
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c.card_type, CardType::Attack));
+
+        for (attacker_index, attacker) in attackers {
+            let blocker = opponent
+                .arena
+                .iter()
+                .position(|c| matches!(c.card_type, CardType::Defense));
+            events.push(GameEvent::Attacked {
+                player: side,
+                attacker: attacker_index,
+                blocker,
+            });
+
+            match blocker {
+                Some(ind) => {
+                    let card = &mut opponent.arena[ind];
+                    card.damage += attacker.power;
+                    if card.damage >= card.power {
+                        events.push(GameEvent::Destroyed {
+                            player: side.other(),
+                            arena_index: ind,
+                            card: card.clone(),
+                        });
+                        opponent.destroy(ind);
+                    }
+                }
+                None => opponent.take_damage(attacker.power),
+            }
+        }
+        self.events.extend(events);
+    }
+
+    /// Apply the effects of a card the active player just played.
+    fn resolve_effects(&mut self, effects: &[Effect]) {
+        let side = self.turn;
+        for effect in effects {
+            let (player, opponent) = self.players_mut();
+            match *effect {
+                Effect::Draw(n) => self.draw(side, n as usize),
+                Effect::Damage(n) => opponent.take_damage(n),
+                Effect::Heal(n) => player.heal(n),
+                Effect::Shield(n) => player.shield += n,
+                Effect::Discard(n) => self.discard(side.other(), |p| p.discard(n as usize)),
+            }
+        }
+    }
+
+    /// Resolve the active player's decision on their opening hand, then
+    /// let the other player decide, or start the first turn.
+    fn mulligan(&mut self, choice: Mulligan) {
+        let side = self.turn;
+        match choice {
+            Mulligan::Keep => {}
+            Mulligan::Redraw => {
+                let count = self.player(side).hand.len();
+                self.put_on_bottom(side, |_| true);
+                let (player, rng) = match side {
+                    Tern::Player1 => (&mut self.player1, &mut self.rng),
+                    Tern::Player2 => (&mut self.player2, &mut self.rng),
+                };
+                rng.shuffle(&mut player.library);
+                self.events.push(GameEvent::Shuffled { player: side });
+                self.deal(side, count);
+            }
+            Mulligan::Bottom(cards) => {
+                self.put_on_bottom(side, |ind| {
+                    cards.checked_shr(ind as u32).unwrap_or(0) & 1 != 0
+                });
+                self.deal(side, cards.count_ones() as usize);
+            }
+        }
+
+        match side {
+            Tern::Player1 => self.turn = Tern::Player2,
+            Tern::Player2 => {
+                self.turn = Tern::Player1;
+                self.phase = Phase::Draw;
+            }
+        }
+    }
+
+    /// Deal `count` cards into the hand of `side`, recording each one.
+    fn deal(&mut self, side: Tern, count: usize) {
+        let player = self.player_mut(side);
+        let before = player.hand.len();
+        player.deal(count);
+
+        let dealt: Vec<_> = player.hand[before..].to_vec();
+        self.events.extend(
+            dealt
+                .into_iter()
+                .map(|card| GameEvent::Drew { player: side, card }),
+        );
+    }
+
+    /// Put the hand cards of `side` whose index is `selected` on the bottom
+    /// of the library, recording each one.
+    fn put_on_bottom(&mut self, side: Tern, selected: impl Fn(usize) -> bool) {
+        let player = self.player_mut(side);
+        let mut events = Vec::new();
+        // from the back, so the indexes of the cards still to go stay put
+        for hand_index in (0..player.hand.len()).rev().filter(|&ind| selected(ind)) {
+            let card = player.hand[hand_index].clone();
+            player.put_on_bottom(hand_index);
+            events.push(GameEvent::Returned {
+                player: side,
+                hand_index,
+                card,
+            });
+        }
+        self.events.extend(events);
+    }
+
+    /// Let `side` draw `count` cards, recording each one drawn.
+    fn draw(&mut self, side: Tern, count: usize) {
+        let player = self.player_mut(side);
+        let before = player.hand.len();
+        player.draw(count);
+
+        let drawn: Vec<_> = player.hand[before..].to_vec();
+        self.events.extend(
+            drawn
+                .into_iter()
+                .map(|card| GameEvent::Drew { player: side, card }),
+        );
+    }
+
+    /// Run `discard` on the player of `side`, recording every card it moved
+    /// from the end of the hand to the graveyard.
+    fn discard(&mut self, side: Tern, discard: impl FnOnce(&mut Player)) {
+        let player = self.player_mut(side);
+        let before = player.graveyard.len();
+        discard(player);
+
+        // cards leave from the same index, each one shifts the next into place
+        let hand_index = player.hand.len();
+        let discarded: Vec<_> = player.graveyard[before..].to_vec();
+        self.events
+            .extend(discarded.into_iter().map(|card| GameEvent::Discarded {
+                player: side,
+                hand_index,
+                card,
+            }));
+    }
+
+    fn end_turn(&mut self) {
+        self.discard(self.turn, Player::cleanup);
+        self.turn = self.turn.other();
+        self.turn_count += 1;
+        self.phase = Phase::Draw;
+    }
+}
+
+#[test]
+fn game_turn_order_test() {
+    let col = test_collection();
+    let mut state = GameState::new(&col, 7);
+    let end = |player| GameAction::EndPhase { player };
+
+    assert_eq!(
+        (state.turn, state.phase, state.player1.mana),
+        (Tern::Player1, Phase::Main, 1)
+    );
+    assert_eq!(
+        state.apply(end(Tern::Player2)),
+        Err(ActionError::NotYourTurn)
+    );
+    assert_eq!(
+        state.apply(GameAction::PlayCard {
+            player: Tern::Player1,
+            hand_index: 99
+        }),
+        Err(ActionError::NoSuchCard)
+    );
+
+    state.apply(end(Tern::Player1)).unwrap();
+    assert_eq!(state.phase, Phase::Combat);
+    assert_eq!(
+        state.apply(GameAction::PlayCard {
+            player: Tern::Player1,
+            hand_index: 0
+        }),
+        Err(ActionError::WrongPhase)
+    );
+
+    // the second player draws on their first turn, the first player did not
+    state.apply(end(Tern::Player1)).unwrap();
+    assert_eq!((state.turn, state.turn_count), (Tern::Player2, 2));
+    assert_eq!(state.player1.hand.len(), 3);
+    assert_eq!(state.player2.hand.len(), 4);
+}
+
+#[test]
+fn game_replay_is_deterministic_test() {
+    let col = test_collection();
+    let mut state = GameState::new(&col, 1234);
+    let mut replay = Replay::new(1234, [None, None], Opening::default());
+
+    // both sides play their first card and pass until someone falls
+    while state.is_running && replay.actions.len() < 200 {
+        let player = state.turn;
+        let action = match state.apply(GameAction::PlayCard {
+            player,
+            hand_index: 0,
+        }) {
+            Ok(()) => GameAction::PlayCard {
+                player,
+                hand_index: 0,
+            },
+            Err(_) => {
+                state.apply(GameAction::EndPhase { player }).unwrap();
+                GameAction::EndPhase { player }
+            }
+        };
+        replay.actions.push(action);
+    }
+
+    let replayed =
+        GameState::from_replay(&col, &Replay::from_json(&replay.to_json()).unwrap()).unwrap();
+    assert!(!state.is_running);
+    assert_eq!(replayed.winner, state.winner);
+    assert_eq!(replayed.turn_count, state.turn_count);
+    assert_eq!(replayed.player1.life, state.player1.life);
+    assert_eq!(replayed.player2.life, state.player2.life);
+
+    // a replay from before the version was stored was dealt another way
+    let old = r#"{"seed":1234,"actions":[]}"#;
+    assert!(Replay::from_json(old).is_err());
+}
+
+#[test]
+fn game_events_test() {
+    let col = test_collection();
+    let mut state = GameState::new(&col, 7);
+
+    // dealing shuffles each library and draws the opening hands
+    assert!(matches!(
+        state.events[0],
+        GameEvent::Shuffled {
+            player: Tern::Player1
+        }
+    ));
+    let drawn = |state: &GameState, side| {
+        state
+            .events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::Drew { player, .. } if *player == side))
+            .count()
+    };
+    assert_eq!(drawn(&state, Tern::Player1), state.player1.hand.len());
+    assert_eq!(drawn(&state, Tern::Player2), state.player2.hand.len());
+
+    let play = GameAction::PlayCard {
+        player: Tern::Player1,
+        hand_index: 99,
+    };
+    assert!(state.apply(play).is_err());
+    assert!(
+        !state.events.is_empty(),
+        "a rejected action changes nothing"
+    );
+
+    let first = state.player1.hand[0].name.clone();
+    state
+        .apply(GameAction::PlayCard {
+            player: Tern::Player1,
+            hand_index: 0,
+        })
+        .unwrap();
+    assert!(matches!(
+        &state.events[0],
+        GameEvent::Played { hand_index: 0, card, .. } if card.name == first
+    ));
+
+    // ending the turn hands over to player 2, who draws one card
+    state
+        .apply(GameAction::EndPhase {
+            player: Tern::Player1,
+        })
+        .unwrap();
+    state
+        .apply(GameAction::EndPhase {
+            player: Tern::Player1,
+        })
+        .unwrap();
+    assert_eq!(drawn(&state, Tern::Player2), 1);
+}
+
+#[test]
+fn game_with_deck_test() {
+    let col = test_collection();
+    let soldiers = Deck {
+        name: "Soldiers".to_string(),
+        cards: vec!["Soldier".to_string(); crate::DECK_SIZE],
+    };
+    let decks = [Some(soldiers), None];
+
+    let state = GameState::with_decks(&col, 7, &decks);
+    let player = &state.player1;
+    assert_eq!(player.library.len() + player.hand.len(), crate::DECK_SIZE);
+    assert!(
+        player
+            .library
+            .iter()
+            .chain(&player.hand)
+            .all(|c| c.name == "Soldier")
+    );
+
+    // the decks are part of the replay
+    let mut replay = Replay::new(7, decks, Opening::default());
+    replay.actions.push(GameAction::PlayCard {
+        player: Tern::Player1,
+        hand_index: 0,
+    });
+    let replayed =
+        GameState::from_replay(&col, &Replay::from_json(&replay.to_json()).unwrap()).unwrap();
+    assert_eq!(replayed.player1.arena[0].name, "Soldier");
+    assert_eq!(replayed.player2.library.len(), state.player2.library.len());
+}
+
+#[test]
+fn game_mulligan_test() {
+    let col = test_collection();
+    let opening = Opening {
+        hand_size: 4,
+        mulligan: true,
+        compensation: Compensation::ExtraCard,
+    };
+    let mut state = GameState::with_opening(&col, 7, &[None, None], opening);
+    let mulligan = |player, choice| GameAction::Mulligan { player, choice };
+
+    // nothing is played before both players decided on their hands
+    assert_eq!((state.turn, state.phase), (Tern::Player1, Phase::Mulligan));
+    assert_eq!(state.player1.hand.len(), 4);
+    assert_eq!(state.player2.hand.len(), 5);
+    assert_eq!(
+        state.apply(GameAction::EndPhase {
+            player: Tern::Player1
+        }),
+        Err(ActionError::WrongPhase)
+    );
+    assert_eq!(
+        state.apply(mulligan(Tern::Player1, Mulligan::Bottom(1 << 4))),
+        Err(ActionError::NoSuchCard)
+    );
+
+    // the first and third card go under the library, two new ones come in
+    let kept = [
+        state.player1.hand[1].name.clone(),
+        state.player1.hand[3].name.clone(),
+    ];
+    let bottom = state.player1.hand[0].name.clone();
+    state
+        .apply(mulligan(Tern::Player1, Mulligan::Bottom(0b0101)))
+        .unwrap();
+    assert_eq!(state.player1.hand.len(), 4);
+    assert_eq!(state.player1.hand[0].name, kept[0]);
+    assert_eq!(state.player1.hand[1].name, kept[1]);
+    assert_eq!(state.player1.library[0].name, bottom);
+    assert!(matches!(
+        state.events[0],
+        GameEvent::Returned { hand_index: 2, .. }
+    ));
+
+    state
+        .apply(mulligan(Tern::Player2, Mulligan::Redraw))
+        .unwrap();
+    assert_eq!(state.player2.hand.len(), 5);
+    assert_eq!(
+        state.player2.hand.len() + state.player2.library.len(),
+        crate::LIBRARY_SIZE
+    );
+    assert_eq!((state.turn, state.phase), (Tern::Player1, Phase::Main));
+    assert_eq!(
+        state.apply(mulligan(Tern::Player1, Mulligan::Keep)),
+        Err(ActionError::WrongPhase)
+    );
+
+    // the same decisions replay to the same hands
+    let mut replay = Replay::new(7, [None, None], opening);
+    replay.actions = vec![
+        mulligan(Tern::Player1, Mulligan::Bottom(0b0101)),
+        mulligan(Tern::Player2, Mulligan::Redraw),
+    ];
+    let replayed =
+        GameState::from_replay(&col, &Replay::from_json(&replay.to_json()).unwrap()).unwrap();
+    let names = |p: &Player| p.hand.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&replayed.player2), names(&state.player2));
+
+    // the extra card never takes a hand past its limit
+    let full = Opening {
+        hand_size: MAX_HAND_SIZE,
+        ..opening
+    };
+    let state = GameState::with_opening(&col, 7, &[None, None], full);
+    assert_eq!(state.player1.hand.len(), MAX_HAND_SIZE);
+    assert_eq!(state.player2.hand.len(), MAX_HAND_SIZE);
+}
+
+#[test]
+fn game_extra_mana_test() {
+    let col = test_collection();
+    let opening = Opening {
+        compensation: Compensation::ExtraMana,
+        ..Opening::default()
+    };
+    let mut state = GameState::with_opening(&col, 7, &[None, None], opening);
+    assert_eq!(state.player1.mana, 1);
+
+    for _ in 0..2 {
+        state
+            .apply(GameAction::EndPhase {
+                player: Tern::Player1,
+            })
+            .unwrap();
+    }
+    assert_eq!((state.turn, state.player2.mana), (Tern::Player2, 2));
+}
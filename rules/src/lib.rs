@@ -0,0 +1,20 @@
+//! Game rules of Opinion without any rendering, shared by the game client
+//! and the API so both agree on what a match looks like.
+
+mod art;
+mod card;
+mod deck;
+mod game;
+mod opening;
+mod player;
+mod rng;
+
+pub use card::{
+    Card, CardCollection, CardImage, CardLoadError, CardType, DEFAULT_PALETTE, Effect,
+    PaletteColor, Rgba,
+};
+pub use deck::{DECK_SIZE, Deck, DeckError, MAX_COPIES, MAX_DECK_NAME_LEN};
+pub use game::{ActionError, GameAction, GameEvent, GameState, Phase, RULES_VERSION, Replay, Tern};
+pub use opening::{Compensation, Mulligan, Opening};
+pub use player::{LIBRARY_SIZE, MAX_HAND_SIZE, MAX_LIFE, MAX_MANA, OPENING_HAND_SIZE, Player};
+pub use rng::SimpleRng;
@@ -0,0 +1,72 @@
+//! How a match starts: the opening hands, the mulligan and what makes up
+//! for going second.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::OPENING_HAND_SIZE;
+
+/// What the second player gets for not starting the match. The starting
+/// player always skips their first draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compensation {
+    None,
+    /// Dealt one card more than the opening hand.
+    ExtraCard,
+    /// One more mana on their first turn.
+    ExtraMana,
+}
+
+impl fmt::Display for Compensation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compensation::None => write!(f, "nothing"),
+            Compensation::ExtraCard => write!(f, "an extra card"),
+            Compensation::ExtraMana => write!(f, "an extra mana"),
+        }
+    }
+}
+
+/// The opening rules of a match. They are part of its `Replay`, every copy
+/// of the match has to deal the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Opening {
+    /// Cards dealt into each hand, at most `MAX_HAND_SIZE`.
+    pub hand_size: usize,
+    /// Whether each player gets one `Mulligan` decision before the first turn.
+    pub mulligan: bool,
+    pub compensation: Compensation,
+}
+
+/// The classic opening, replays recorded before there was a choice use it.
+impl Default for Opening {
+    fn default() -> Self {
+        Self {
+            hand_size: OPENING_HAND_SIZE,
+            mulligan: false,
+            compensation: Compensation::None,
+        }
+    }
+}
+
+impl Opening {
+    /// The opening local matches start with until the player changes it.
+    pub fn standard() -> Self {
+        Self {
+            hand_size: OPENING_HAND_SIZE,
+            mulligan: true,
+            compensation: Compensation::ExtraCard,
+        }
+    }
+}
+
+/// A player's decision about their opening hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mulligan {
+    Keep,
+    /// Shuffle the whole hand back into the library and draw as many cards.
+    Redraw,
+    /// Put the hand cards whose bits are set, bit `i` for hand index `i`,
+    /// on the bottom of the library and draw as many cards.
+    Bottom(u32),
+}
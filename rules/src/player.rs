@@ -0,0 +1,198 @@
+//! One side of a match: its card piles, life and mana.
+
+use crate::{Card, CardCollection, Deck, Effect, SimpleRng};
+
+/// Life each player starts the match with, and the most they can heal to.
+pub const MAX_LIFE: u32 = 20;
+
+/// Mana pool growth stops here.
+pub const MAX_MANA: u32 = 10;
+
+/// Cards above this count are discarded at the end of the turn.
+/// Matches the 7 card slots of the hand rect.
+pub const MAX_HAND_SIZE: usize = 7;
+
+/// Cards dealt into each player's library at the start of a match.
+pub const LIBRARY_SIZE: usize = 20;
+
+/// Cards dealt into each hand at the start of a match, unless its
+/// `Opening` says otherwise.
+pub const OPENING_HAND_SIZE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub hand: Vec<Card>,
+    pub arena: Vec<Card>,
+    pub library: Vec<Card>,
+    pub graveyard: Vec<Card>,
+
+    pub life: u32,
+    pub shield: u32,
+    /// Mana left to spend this turn.
+    pub mana: u32,
+    /// Mana the pool refills to at the start of each turn.
+    pub max_mana: u32,
+    /// Set when the player had to draw from an empty library.
+    pub decked_out: bool,
+}
+
+impl Player {
+    /// Player with `deck` shuffled into their library, or `LIBRARY_SIZE`
+    /// random cards without one. The hand is empty until it is dealt.
+    pub fn new(col: &CardCollection, deck: Option<&Deck>, rng: &mut SimpleRng) -> Self {
+        let library = match deck {
+            Some(deck) => {
+                let mut cards = deck.cards(col);
+                rng.shuffle(&mut cards);
+                cards
+            }
+            None => col.get_random(rng, LIBRARY_SIZE),
+        };
+
+        Self {
+            hand: Vec::new(),
+            arena: Vec::new(),
+            library,
+            graveyard: Vec::new(),
+            life: MAX_LIFE,
+            shield: 0,
+            mana: 0,
+            max_mana: 0,
+            decked_out: false,
+        }
+    }
+
+    /// Move up to `count` cards from the top of the library into the hand,
+    /// like `deal`. Drawing more cards than the library holds decks the
+    /// player out.
+    pub fn draw(&mut self, count: usize) {
+        if count > self.library.len() {
+            self.decked_out = true;
+        }
+        self.deal(count);
+    }
+
+    /// Move up to `count` cards from the top of the library into the hand,
+    /// one at a time, so the top card comes first. Unlike `draw` this never
+    /// decks out. Replays refer to hand indices, changing the order needs a
+    /// new `RULES_VERSION`.
+    pub fn deal(&mut self, count: usize) {
+        let dealt = self.library.len().saturating_sub(count);
+        self.hand.extend(self.library.drain(dealt..).rev());
+    }
+
+    /// Move the hand card at `ind` to the bottom of the library.
+    pub fn put_on_bottom(&mut self, ind: usize) {
+        if ind < self.hand.len() {
+            let card = self.hand.remove(ind);
+            self.library.insert(0, card);
+        }
+    }
+
+    /// Grow the mana pool by one and refill it.
+    pub fn start_turn(&mut self) {
+        self.max_mana = (self.max_mana + 1).min(MAX_MANA);
+        self.mana = self.max_mana;
+    }
+
+    pub fn can_afford(&self, card: &Card) -> bool {
+        card.cost <= self.mana
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        self.life == 0 || self.decked_out
+    }
+
+    /// Lose `amount` life, spending shield first.
+    pub fn take_damage(&mut self, amount: u32) {
+        let blocked = amount.min(self.shield);
+        self.shield -= blocked;
+        self.life = self.life.saturating_sub(amount - blocked);
+    }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.life = (self.life + amount).min(MAX_LIFE);
+    }
+
+    /// Move up to `count` cards from the end of the hand to the graveyard.
+    pub fn discard(&mut self, count: usize) {
+        let kept = self.hand.len().saturating_sub(count);
+        self.graveyard.extend(self.hand.drain(kept..));
+    }
+
+    /// Discard from the end of the hand until it fits `MAX_HAND_SIZE`.
+    pub fn cleanup(&mut self) {
+        if self.hand.len() > MAX_HAND_SIZE {
+            self.graveyard.extend(self.hand.drain(MAX_HAND_SIZE..));
+        }
+    }
+
+    /// Pay for the hand card at `ind` and put it into play. Attack and
+    /// Defense cards stay in the arena, the rest go straight to the graveyard.
+    /// Returns the effects of the played card for the caller to resolve.
+    pub fn play_card(&mut self, ind: usize) -> Option<Vec<Effect>> {
+        match self.hand.get(ind) {
+            Some(card) if self.can_afford(card) => {}
+            _ => return None,
+        }
+
+        let card = self.hand.remove(ind);
+        self.mana -= card.cost;
+        let effects = card.effects.clone();
+        if card.card_type.is_permanent() {
+            self.arena.push(card);
+        } else {
+            self.graveyard.push(card);
+        }
+        Some(effects)
+    }
+
+    /// Move the arena card at `ind` to the graveyard.
+    pub fn destroy(&mut self, ind: usize) {
+        if ind < self.arena.len() {
+            let card = self.arena.remove(ind);
+            self.graveyard.push(card);
+        }
+    }
+}
+
+#[test]
+fn player_draw_and_damage_test() {
+    let mut player = Player {
+        hand: Vec::new(),
+        arena: Vec::new(),
+        library: Vec::new(),
+        graveyard: Vec::new(),
+        life: MAX_LIFE,
+        shield: 3,
+        mana: 0,
+        max_mana: 0,
+        decked_out: false,
+    };
+
+    player.take_damage(5);
+    assert_eq!((player.shield, player.life), (0, MAX_LIFE - 2));
+    player.heal(100);
+    assert_eq!(player.life, MAX_LIFE);
+
+    player.draw(1);
+    assert!(player.decked_out);
+    assert!(player.is_defeated());
+
+    // dealt and drawn cards both come into the hand top card first
+    let card = |name| {
+        Card::new(
+            crate::CardImage::new(1, 1),
+            name,
+            Vec::new(),
+            1,
+            1,
+            crate::CardType::Attack,
+        )
+    };
+    player.library = ["e", "d", "c", "b", "a"].map(card).to_vec();
+    player.deal(2);
+    player.draw(2);
+    let names: Vec<_> = player.hand.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "c", "d"]);
+}
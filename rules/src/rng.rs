@@ -0,0 +1,328 @@
+//! Deterministic RNG shared by every copy of a match.
+
+#[derive(Clone, Copy, Debug)]
+pub struct SimpleRng {
+    state: u64,
+}
+
+/// Provided by `public/index.html` on the web build.
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    fn get_seed() -> u64;
+}
+
+impl SimpleRng {
+    /// Create a new RNG from a user-provided seed.
+    pub fn from_seed(seed: u64) -> Self {
+        // avoid zero state (xorshift degenerate)
+        let state = if seed == 0 {
+            0x9e3779b97f4a7c15u64
+        } else {
+            seed
+        };
+        SimpleRng { state }
+    }
+
+    /// Create an RNG with a simple default seed.
+    /// On native, this will try to use the current time; on wasm it falls back to a fixed value.
+    pub fn from_entropy() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use std::time::{SystemTime, UNIX_EPOCH};
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+            // mix with address of a local var for tiny extra variability
+            let mix = (&nanos as *const u64 as u64).wrapping_mul(0xf39cc0605cedc835u64);
+            SimpleRng::from_seed(nanos ^ mix)
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            // wasm: avoid pulling in web-sys/wasm-bindgen here.
+            // If you want real entropy in wasm, pass a seed from JS (e.g. crypto.getRandomValues).
+            // SimpleRng::from_seed(0x1234_5678_dead_beefu64)
+            let seed = unsafe { get_seed() };
+            SimpleRng::from_seed(seed)
+        }
+    }
+
+    /// xorshift64* implementation -> returns 64-bit random value
+    pub fn next_u64(&mut self) -> u64 {
+        // xorshift64* from Marsaglia / Sebastiano Vigna variant
+        let mut x = self.state;
+        // x must be non-zero; we enforced in from_seed
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(2685821657736338717u64)
+    }
+
+    /// 32-bit random
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// generate a number in range [start, end) (usize), every value equally likely.
+    /// Returns `start` for an empty range.
+    pub fn gen_range(&mut self, start: usize, end: usize) -> usize {
+        let len = end.saturating_sub(start);
+        if len == 0 {
+            return start;
+        }
+        start + self.below(len as u64) as usize
+    }
+
+    /// Uniform value in [0, n) for n > 0, Lemire's multiply and shift. The
+    /// few raw values that would favour the low end are drawn again.
+    fn below(&mut self, n: u64) -> u64 {
+        // 2^64 mod n, the size of the biased zone
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let m = self.next_u64() as u128 * n as u128;
+            if m as u64 >= threshold {
+                return (m >> 64) as u64;
+            }
+        }
+    }
+
+    /// Uniform f64 in [0, 1), from the top 53 bits.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniform f32 in [0, 1), from the top 24 bits.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+
+    /// true with probability `p`, so never for 0 and always for 1.
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Index into `weights` picked with probability proportional to its
+    /// weight, e.g. card rarities. `None` when all weights are zero.
+    pub fn weighted_index(&mut self, weights: &[u32]) -> Option<usize> {
+        let total: u64 = weights.iter().map(|&w| w as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = self.below(total);
+        for (ind, &weight) in weights.iter().enumerate() {
+            if pick < weight as u64 {
+                return Some(ind);
+            }
+            pick -= weight as u64;
+        }
+        unreachable!("pick is below the total weight")
+    }
+
+    /// Item of `items` picked with probability proportional to `weight`.
+    pub fn choose_weighted<'a, T>(
+        &mut self,
+        items: &'a [T],
+        weight: impl Fn(&T) -> u32,
+    ) -> Option<&'a T> {
+        let weights: Vec<u32> = items.iter().map(weight).collect();
+        self.weighted_index(&weights).map(|ind| &items[ind])
+    }
+
+    /// Generator for one system, like the AI or visual effects, derived from
+    /// this one and `id` without advancing it. Different ids give unrelated
+    /// sequences, so one system drawing more numbers never shifts another.
+    pub fn stream(&self, id: u64) -> SimpleRng {
+        SimpleRng::from_seed(splitmix64(self.state ^ splitmix64(id)))
+    }
+
+    /// Fisher-Yates shuffle using this RNG
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        let n = slice.len();
+        if n <= 1 {
+            return;
+        }
+        for i in (1..n).rev() {
+            let j = self.gen_range(0, i + 1);
+            slice.swap(i, j);
+        }
+    }
+
+    /// Choose `k` distinct items from `items` (no replacement). Returns owned `Vec<T>` by cloning.
+    /// Requires T: Clone. Order of returned elements is random.
+    pub fn choose_multiple_without_replacement<T: Clone>(
+        &mut self,
+        items: &[T],
+        k: usize,
+    ) -> Vec<T> {
+        let k = k.min(items.len());
+        // If k is close to items.len(), it's cheaper to shuffle indices or copy then shuffle.
+        // We'll copy indices, shuffle, and take first k.
+        let mut idxs: Vec<usize> = (0..items.len()).collect();
+        self.shuffle(&mut idxs);
+        idxs.into_iter().take(k).map(|i| items[i].clone()).collect()
+    }
+
+    /// Choose `k` items with replacement (duplicates allowed)
+    pub fn choose_multiple_with_replacement<T: Clone>(&mut self, items: &[T], k: usize) -> Vec<T> {
+        if items.is_empty() || k == 0 {
+            return Vec::new();
+        }
+        (0..k)
+            .map(|_| {
+                let i = self.gen_range(0, items.len());
+                items[i].clone()
+            })
+            .collect()
+    }
+
+    /// Remove `n` random elements from the back-ish (i.e. pick n random indices and remove them).
+    /// This removes by swapping each chosen element to the end and popping — more efficient than repeated remove.
+    /// Returns removed elements in arbitrary order.
+    pub fn remove_random_n<T>(&mut self, vec: &mut Vec<T>, n: usize) -> Vec<T> {
+        let take = n.min(vec.len());
+        let mut removed = Vec::with_capacity(take);
+        // We will pick `take` distinct indices by performing a partial Fisher-Yates on indices.
+        // Equivalent to picking `take` unique random elements.
+        let mut i = vec.len();
+        for _ in 0..take {
+            i -= 1;
+            // pick j in [0..=i]
+            let j = self.gen_range(0, i + 1);
+            vec.swap(i, j);
+            // pop element at end (which was at j)
+            if let Some(x) = vec.pop() {
+                removed.push(x);
+            }
+        }
+        removed
+    }
+}
+
+/// SplitMix64 finalizer, spreads every input bit over the whole output.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn rng_same_seed_test() {
+    let mut a = SimpleRng::from_seed(42);
+    let mut b = SimpleRng::from_seed(42);
+    for _ in 0..100 {
+        assert_eq!(a.next_u64(), b.next_u64());
+    }
+
+    let mut zero = SimpleRng::from_seed(0);
+    assert_ne!(zero.next_u64(), 0);
+}
+
+#[test]
+fn rng_fixed_seed_sequence_test() {
+    // replays and online matches are dealt from these, changing them means
+    // bumping `RULES_VERSION`
+    let mut rng = SimpleRng::from_seed(42);
+    let raw: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+    assert_eq!(
+        raw,
+        [
+            6255019084209693600,
+            14430073426741505498,
+            14575455857230217846
+        ]
+    );
+    let rolls: Vec<usize> = (0..8).map(|_| rng.gen_range(0, 10)).collect();
+    assert_eq!(rolls, [9, 7, 8, 2, 4, 3, 6, 4]);
+    assert_eq!(rng.next_f64(), 0.3401489376037601);
+    assert_eq!(rng.next_f32(), 0.8755317);
+
+    let mut cards: Vec<u32> = (0..8).collect();
+    SimpleRng::from_seed(42).shuffle(&mut cards);
+    assert_eq!(cards, [1, 0, 7, 3, 6, 4, 5, 2]);
+}
+
+#[test]
+fn rng_gen_range_test() {
+    let mut rng = SimpleRng::from_seed(7);
+    assert_eq!(rng.gen_range(5, 5), 5);
+    assert_eq!(rng.gen_range(5, 3), 5);
+    assert_eq!(rng.gen_range(3, 4), 3);
+
+    // every value comes up about as often, the last one included
+    const DRAWS: usize = 60_000;
+    let mut counts = [0usize; 6];
+    for _ in 0..DRAWS {
+        counts[rng.gen_range(10, 16) - 10] += 1;
+    }
+    let expected = DRAWS / counts.len();
+    for count in counts {
+        assert!(count.abs_diff(expected) < expected / 20, "{:?}", counts);
+    }
+
+    // a range just over half of u64 is where modulo would be worst off
+    let n = u64::MAX / 2 + 2;
+    let low = (0..10_000).filter(|_| rng.below(n) < n / 2).count();
+    assert!(low.abs_diff(5_000) < 250, "{}", low);
+}
+
+#[test]
+fn rng_float_test() {
+    let mut rng = SimpleRng::from_seed(99);
+    const DRAWS: usize = 20_000;
+    let (mut sum64, mut sum32) = (0.0, 0.0);
+    for _ in 0..DRAWS {
+        let (x, y) = (rng.next_f64(), rng.next_f32());
+        assert!((0.0..1.0).contains(&x));
+        assert!((0.0..1.0).contains(&y));
+        sum64 += x;
+        sum32 += y as f64;
+    }
+    assert!((sum64 / DRAWS as f64 - 0.5).abs() < 0.01);
+    assert!((sum32 / DRAWS as f64 - 0.5).abs() < 0.01);
+
+    assert!(!(0..100).any(|_| rng.gen_bool(0.0)));
+    assert!((0..100).all(|_| rng.gen_bool(1.0)));
+}
+
+#[test]
+fn rng_weighted_test() {
+    let mut rng = SimpleRng::from_seed(3);
+    assert_eq!(rng.weighted_index(&[]), None);
+    assert_eq!(rng.weighted_index(&[0, 0]), None);
+
+    const DRAWS: usize = 50_000;
+    let weights = [1, 3, 0, 6];
+    let mut counts = [0usize; 4];
+    for _ in 0..DRAWS {
+        counts[rng.weighted_index(&weights).unwrap()] += 1;
+    }
+    assert_eq!(counts[2], 0, "zero weight is never picked");
+    for (count, weight) in counts.iter().zip(weights) {
+        let expected = DRAWS * weight as usize / 10;
+        assert!(count.abs_diff(expected) < DRAWS / 100, "{:?}", counts);
+    }
+
+    let rarities = [("common", 9), ("rare", 1)];
+    let rare = (0..DRAWS)
+        .filter(|_| rng.choose_weighted(&rarities, |r| r.1).unwrap().0 == "rare")
+        .count();
+    assert!(rare.abs_diff(DRAWS / 10) < DRAWS / 100);
+}
+
+#[test]
+fn rng_stream_test() {
+    let rng = SimpleRng::from_seed(42);
+    let sequence = |mut rng: SimpleRng| (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>();
+
+    // a stream neither moves its parent nor depends on anything but the parent and id
+    assert_eq!(sequence(rng.stream(1)), sequence(rng.stream(1)));
+    assert_eq!(sequence(rng), sequence(SimpleRng::from_seed(42)));
+    assert_eq!(rng.stream(1).next_u64(), 120295383539102161);
+
+    assert_ne!(sequence(rng.stream(1)), sequence(rng.stream(2)));
+    assert_ne!(sequence(rng.stream(0)), sequence(rng));
+}